@@ -0,0 +1,123 @@
+// Timings of opening, editing, drawing and finding in documents of growing
+// size, which should stay flat apart from opening and finding. Everything
+// after opening is timed once the document has been edited in thousands of
+// places, as it would be over a long session. They are ignored tests so they
+// only run when asked for:
+//
+//     cargo test --release bench -- --ignored --nocapture
+
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::document::Cursor;
+use crate::document::Document;
+use crate::pattern::Flags;
+use crate::pattern::Pattern;
+use crate::terminal::Event;
+use crate::ui::Component;
+use crate::ui::TextArea;
+
+const LINES: [usize; 3] = [10_000, 100_000, 1_000_000];
+// edits made all over the document before it is timed
+const SCATTERED: usize = 5_000;
+
+fn text(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("{} the quick brown fox jumps over the lazy dog\n", i))
+        .collect()
+}
+
+fn time(runs: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..runs {
+        f();
+    }
+
+    start.elapsed() / runs
+}
+
+fn report(name: &str, timings: &[Duration]) {
+    let columns: Vec<String> = timings.iter().map(|t| format!("{:>12.1?}", t)).collect();
+
+    println!("{:<8}{}", name, columns.join(""));
+}
+
+#[test]
+#[ignore]
+fn bench_document() {
+    let mut open = vec![];
+    let mut edit = vec![];
+    let mut first_draw = vec![];
+    let mut keystroke = vec![];
+    let mut find = vec![];
+
+    for lines in LINES {
+        let text = text(lines);
+        let middle = Cursor {
+            x: 10,
+            y: lines / 2,
+        };
+
+        open.push(time(3, || {
+            Document::from_reader(text.as_bytes()).unwrap();
+        }));
+
+        let mut document = Document::from_reader(text.as_bytes()).unwrap();
+
+        // each edit splits the text where it is made
+        for i in 0..SCATTERED {
+            document.go_to(i * 7919 % lines + 1, 5);
+            document.insert("x");
+        }
+
+        document.seal_history();
+        document.cursor = middle;
+
+        // typing a word and a new line, then deleting them again
+        edit.push(time(100, || {
+            for c in ["a", "b", "c"] {
+                document.insert(c);
+            }
+            document.insert_line();
+
+            for _ in 0..4 {
+                document.delete_prev();
+            }
+        }));
+
+        // finding a word on every line, and then one spanning two lines
+        let word = Pattern::new("fox", Flags::default()).unwrap();
+        let across = Pattern::new("dog\\n1", Flags::default()).unwrap();
+
+        find.push(time(1, || {
+            assert_eq!(document.find_all(&word).len(), lines);
+            document.find_all(&across);
+        }));
+
+        // the first draw measures every row, and the next only the edits
+        let mut text_area = TextArea::new(document);
+
+        first_draw.push(time(1, || {
+            text_area.render(80, 50);
+        }));
+
+        keystroke.push(time(100, || {
+            text_area
+                .update(&Event::Input("a".to_string()), 80)
+                .unwrap();
+            text_area.render(80, 50);
+        }));
+    }
+
+    println!(
+        "{:<8}{}",
+        "lines",
+        LINES.map(|n| format!("{:>12}", n)).join("")
+    );
+    report("open", &open);
+    report("edit", &edit);
+    report("draw", &first_draw);
+    report("key", &keystroke);
+    report("find", &find);
+}
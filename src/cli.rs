@@ -0,0 +1,284 @@
+use std::path::Path;
+
+use crate::encoding::Encoding;
+
+// A file named on the command line and where to put the cursor in it.
+// Lines and columns start at 1.
+#[derive(Debug, PartialEq)]
+pub struct Target {
+    pub filename: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+// Splits a `file:LINE` or `file:LINE:COL` argument, unless a file with the
+// whole argument as its name exists.
+fn split_position(arg: &str) -> Target {
+    let target = Target {
+        filename: arg.to_string(),
+        line: None,
+        column: None,
+    };
+
+    if Path::new(arg).exists() {
+        return target;
+    }
+
+    let (rest, last) = match arg.rsplit_once(':') {
+        Some((rest, last)) if !rest.is_empty() => (rest, last),
+        _ => return target,
+    };
+
+    let last: usize = match last.parse() {
+        Ok(n) => n,
+        Err(_) => return target,
+    };
+
+    if let Some((name, line)) = rest.rsplit_once(':')
+        && let Ok(line) = line.parse()
+        && !name.is_empty()
+    {
+        return Target {
+            filename: name.to_string(),
+            line: Some(line),
+            column: Some(last),
+        };
+    }
+
+    Target {
+        filename: rest.to_string(),
+        line: Some(last),
+        column: None,
+    }
+}
+
+pub const USAGE: &str = "\
+Usage: takkun [OPTIONS] [FILE]...
+
+Opens each FILE in a tab of its own. `+LINE FILE`, `FILE:LINE` and
+`FILE:LINE:COLUMN` put the cursor at a position in the file. A FILE of `-`
+reads text from stdin, as is done whenever stdin isn't a terminal.
+
+Options:
+  -h, --help             Print this help and exit
+  -V, --version          Print the version and exit
+  -R, --readonly         Don't allow changes to any file
+      --config <PATH>    Read settings from PATH instead of
+                         ~/.config/takkun/config
+      --line <LINE>      Put the cursor on LINE in the next file
+      --column <COLUMN>  Put the cursor at COLUMN in the next file
+      --encoding <NAME>  Read and write files as utf-8, utf-16le, utf-16be
+                         or latin1
+      --no-color         Draw without colors
+      --                 Treat the remaining arguments as file names
+";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub targets: Vec<Target>,
+    // whether `-` asked for stdin to be read
+    pub stdin: bool,
+    pub readonly: bool,
+    pub config: Option<String>,
+    pub encoding: Option<Encoding>,
+    pub no_color: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+fn number(option: &str, value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{} needs a number from 1 up, not `{}`",
+            option, value
+        )),
+    }
+}
+
+// Parses the arguments after the program name. Positions given with
+// `--line`, `--column` or `+LINE` apply to the file after them.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut line = None;
+    let mut column = None;
+    let mut files_only = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !files_only && arg.starts_with('-') && arg != "-" {
+            // `--name=value` is the same as `--name value`
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| format!("{} needs a value", name))
+            };
+
+            match name {
+                "--" if inline.is_none() => files_only = true,
+                "-h" | "--help" if inline.is_none() => return Ok(Command::Help),
+                "-V" | "--version" if inline.is_none() => return Ok(Command::Version),
+                "-R" | "--readonly" if inline.is_none() => options.readonly = true,
+                "--no-color" if inline.is_none() => options.no_color = true,
+                "--config" => options.config = Some(value()?),
+                "--encoding" => {
+                    let name = value()?;
+                    let encoding = Encoding::from_name(&name)
+                        .ok_or_else(|| format!("unknown encoding `{}`", name))?;
+                    options.encoding = Some(encoding);
+                }
+                "--line" => line = Some(number(name, &value()?)?),
+                "--column" => column = Some(number(name, &value()?)?),
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+
+            continue;
+        }
+
+        if !files_only && arg == "-" {
+            options.stdin = true;
+            continue;
+        }
+
+        if !files_only && let Some(n) = arg.strip_prefix('+').and_then(|n| n.parse().ok()) {
+            line = Some(n);
+            continue;
+        }
+
+        let mut target = if files_only {
+            Target {
+                filename: arg.clone(),
+                line: None,
+                column: None,
+            }
+        } else {
+            split_position(arg)
+        };
+
+        if line.is_some() || column.is_some() {
+            target.line = line.take();
+            target.column = column.take();
+        }
+
+        options.targets.push(target);
+    }
+
+    if line.is_some() || column.is_some() {
+        return Err("a position has to come before the file it is for".to_string());
+    }
+
+    Ok(Command::Run(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::parse;
+    use crate::cli::Command;
+    use crate::cli::Options;
+    use crate::cli::Target;
+    use crate::encoding::Encoding;
+
+    fn target(filename: &str, line: Option<usize>, column: Option<usize>) -> Target {
+        Target {
+            filename: filename.to_string(),
+            line,
+            column,
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn targets(args: &[String]) -> Vec<Target> {
+        match parse(args) {
+            Ok(Command::Run(options)) => options.targets,
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parses_positions() {
+        assert_eq!(
+            targets(&args(&["a.rs", "+12", "b.rs", "c.rs:3", "d.rs:4:5"])),
+            vec![
+                target("a.rs", None, None),
+                target("b.rs", Some(12), None),
+                target("c.rs", Some(3), None),
+                target("d.rs", Some(4), Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_names_that_only_look_like_positions() {
+        assert_eq!(
+            targets(&args(&["a:b", ":3", "+x", "c:d:7"])),
+            vec![
+                target("a:b", None, None),
+                target(":3", None, None),
+                target("+x", None, None),
+                target("c:d", Some(7), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(
+            parse(&args(&[
+                "-R",
+                "--encoding=latin1",
+                "--config",
+                "takkun.conf",
+                "--no-color",
+                "--line",
+                "3",
+                "--column=4",
+                "a.rs",
+                "-",
+                "--",
+                "-b.rs",
+                "+5",
+                "-",
+            ])),
+            Ok(Command::Run(Options {
+                targets: vec![
+                    target("a.rs", Some(3), Some(4)),
+                    target("-b.rs", None, None),
+                    target("+5", None, None),
+                    target("-", None, None),
+                ],
+                stdin: true,
+                readonly: true,
+                config: Some("takkun.conf".to_string()),
+                encoding: Some(Encoding::Latin1),
+                no_color: true,
+            }))
+        );
+
+        assert_eq!(parse(&args(&["a.rs", "--help"])), Ok(Command::Help));
+        assert_eq!(parse(&args(&["-V"])), Ok(Command::Version));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&args(&["--colour"])).is_err());
+        assert!(parse(&args(&["--config"])).is_err());
+        assert!(parse(&args(&["--line", "0", "a.rs"])).is_err());
+        assert!(parse(&args(&["--encoding", "ebcdic"])).is_err());
+        assert!(parse(&args(&["--readonly=yes"])).is_err());
+        assert!(parse(&args(&["a.rs", "+3"])).is_err());
+    }
+}
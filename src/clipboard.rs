@@ -0,0 +1,188 @@
+use std::env;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+use crate::config;
+use crate::terminal;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Where copied text is sent and pasted text is read from. Selected with the
+// `clipboard` setting, which may be one of `internal`, `osc52`, `wl-copy`,
+// `xclip` or `xsel`. Otherwise the first available helper is picked, falling
+// back to OSC 52.
+pub enum Backend {
+    Internal,
+    Osc52(Box<terminal::Out>),
+    Helper {
+        copy: Vec<&'static str>,
+        paste: Vec<&'static str>,
+    },
+}
+
+fn in_path(program: &str) -> bool {
+    match env::var_os("PATH") {
+        Some(paths) => env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file()),
+        None => false,
+    }
+}
+
+fn wl_copy() -> Backend {
+    Backend::Helper {
+        copy: vec!["wl-copy"],
+        paste: vec!["wl-paste", "--no-newline"],
+    }
+}
+
+fn xclip() -> Backend {
+    Backend::Helper {
+        copy: vec!["xclip", "-selection", "clipboard"],
+        paste: vec!["xclip", "-selection", "clipboard", "-o"],
+    }
+}
+
+fn xsel() -> Backend {
+    Backend::Helper {
+        copy: vec!["xsel", "--clipboard", "--input"],
+        paste: vec!["xsel", "--clipboard", "--output"],
+    }
+}
+
+fn detect() -> Backend {
+    if env::var_os("WAYLAND_DISPLAY").is_some() && in_path("wl-copy") && in_path("wl-paste") {
+        return wl_copy();
+    }
+
+    if env::var_os("DISPLAY").is_some() {
+        if in_path("xclip") {
+            return xclip();
+        }
+
+        if in_path("xsel") {
+            return xsel();
+        }
+    }
+
+    Backend::Osc52(terminal::output())
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - i * 6)) & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+impl Backend {
+    pub fn from_config() -> Backend {
+        match config::get().clipboard.as_deref() {
+            Some("internal") => Backend::Internal,
+            Some("osc52") => Backend::Osc52(terminal::output()),
+            Some("wl-copy") => wl_copy(),
+            Some("xclip") => xclip(),
+            Some("xsel") => xsel(),
+            _ => detect(),
+        }
+    }
+
+    pub fn copy(&self, text: &str) -> io::Result<()> {
+        match self {
+            Backend::Internal => Ok(()),
+            Backend::Osc52(write) => {
+                write(format!("\x1b]52;c;{}\x07", base64(text.as_bytes())).as_bytes())
+            }
+            Backend::Helper { copy, .. } => {
+                let mut child = Command::new(copy[0])
+                    .args(&copy[1..])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(text.as_bytes())?;
+                }
+
+                // a helper that fails, as with no display, would otherwise
+                // leave the clipboard as it was without a word
+                let status = child.wait()?;
+
+                if !status.success() {
+                    return Err(io::Error::other(format!("{} failed ({})", copy[0], status)));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    // Returns the system clipboard contents, or None if this backend can't
+    // read them and the internal clipboard should be used instead.
+    pub fn paste(&self) -> io::Result<Option<String>> {
+        match self {
+            Backend::Internal | Backend::Osc52(_) => Ok(None),
+            Backend::Helper { paste, .. } => {
+                let output = Command::new(paste[0])
+                    .args(&paste[1..])
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::null())
+                    .output();
+
+                // a helper that can't be run or fails, as with no display,
+                // leaves pasting to the internal clipboard
+                let output = match output {
+                    Ok(output) if output.status.success() => output,
+                    _ => return Ok(None),
+                };
+
+                Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clipboard::base64;
+    use crate::clipboard::Backend;
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("ぁ".as_bytes()), "44GB");
+    }
+
+    #[test]
+    fn reports_a_failing_helper() {
+        let backend = Backend::Helper {
+            copy: vec!["sh", "-c", "cat >/dev/null; exit 3"],
+            paste: vec!["sh", "-c", "exit 3"],
+        };
+
+        let error = backend.copy("text").unwrap_err();
+        assert_eq!(error.to_string(), "sh failed (exit status: 3)");
+        assert!(backend.paste().unwrap().is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::encoding::Encoding;
+
+// Settings are read from a config file of `key = value` lines, which the
+// TAKKUN_* environment variables and then command line flags override.
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+const CLIPBOARDS: [&str; 6] = ["auto", "internal", "osc52", "wl-copy", "xclip", "xsel"];
+
+pub struct Config {
+    // lines kept visible above and below the cursor
+    pub scroll_off: usize,
+    // one of CLIPBOARDS, or None to pick one automatically
+    pub clipboard: Option<String>,
+    pub kitty_keyboard: bool,
+    pub encoding: Encoding,
+    pub color: bool,
+    pub readonly: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            scroll_off: 3,
+            clipboard: None,
+            kitty_keyboard: true,
+            encoding: Encoding::Utf8,
+            color: true,
+            readonly: false,
+        }
+    }
+}
+
+// Returns the settings in use, which are the defaults until `set` is called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+pub fn set(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+fn default_path() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("takkun/config")),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(".config/takkun/config")),
+    }
+}
+
+impl Config {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "scrolloff" => {
+                self.scroll_off = value
+                    .parse()
+                    .map_err(|_| format!("scrolloff must be a number, not `{}`", value))?;
+            }
+            "clipboard" if CLIPBOARDS.contains(&value) => {
+                self.clipboard = Some(value).filter(|v| *v != "auto").map(String::from);
+            }
+            "clipboard" => {
+                return Err(format!(
+                    "clipboard must be one of {}, not `{}`",
+                    CLIPBOARDS.join(", "),
+                    value
+                ));
+            }
+            "keyboard" => match value {
+                "kitty" => self.kitty_keyboard = true,
+                "legacy" => self.kitty_keyboard = false,
+                _ => return Err(format!("keyboard must be kitty or legacy, not `{}`", value)),
+            },
+            "encoding" => {
+                self.encoding = Encoding::from_name(value)
+                    .ok_or_else(|| format!("unknown encoding `{}`", value))?;
+            }
+            "color" => match value {
+                "true" => self.color = true,
+                "false" => self.color = false,
+                _ => return Err(format!("color must be true or false, not `{}`", value)),
+            },
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    // Applies the settings in a config file. Blank lines and lines starting
+    // with `#` are skipped.
+    pub fn parse(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", i + 1))?;
+
+            self.set(key.trim(), value.trim())
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+        }
+
+        Ok(())
+    }
+
+    // Reads the config file at `path`, or the default one if there is one,
+    // and then the environment.
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        let (path, required) = match path {
+            Some(path) => (Some(PathBuf::from(path)), true),
+            None => (default_path(), false),
+        };
+
+        if let Some(path) = path {
+            match fs::read_to_string(&path) {
+                Ok(text) => config
+                    .parse(&text)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?,
+                Err(e) if e.kind() == ErrorKind::NotFound && !required => {}
+                Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+            }
+        }
+
+        // invalid values in the environment are ignored
+        for (var, key) in [
+            ("TAKKUN_SCROLLOFF", "scrolloff"),
+            ("TAKKUN_CLIPBOARD", "clipboard"),
+            ("TAKKUN_KEYBOARD", "keyboard"),
+        ] {
+            if let Ok(value) = env::var(var) {
+                let _ = config.set(key, &value);
+            }
+        }
+
+        // https://no-color.org
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            config.color = false;
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::encoding::Encoding;
+
+    #[test]
+    fn parses_settings() {
+        let mut config = Config::default();
+
+        config
+            .parse(
+                "# comment\n\nscrolloff = 5\nclipboard=xsel\n encoding = latin1 \ncolor = false\n",
+            )
+            .unwrap();
+
+        assert_eq!(config.scroll_off, 5);
+        assert_eq!(config.clipboard.as_deref(), Some("xsel"));
+        assert_eq!(config.encoding, Encoding::Latin1);
+        assert!(!config.color);
+
+        config.parse("clipboard = auto").unwrap();
+        assert_eq!(config.clipboard, None);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let mut config = Config::default();
+
+        assert_eq!(
+            config.parse("color = true\nfoo = 1"),
+            Err("line 2: unknown setting `foo`".to_string())
+        );
+        assert!(config.parse("scrolloff").is_err());
+        assert!(config.parse("keyboard = fancy").is_err());
+    }
+}
@@ -1,286 +1,679 @@
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path::Path;
-
-use crate::style::styled;
-use crate::style::tabbed;
+use std::path::PathBuf;
+
+use crate::config;
+use crate::encoding::Encoding;
+use crate::history::Edit;
+use crate::history::History;
+use crate::history::Kind;
+use crate::path;
+use crate::pattern;
+use crate::pattern::Pattern;
+use crate::style::fill;
+use crate::style::push;
+use crate::style::Line;
 use crate::style::Style;
+use crate::text::Text;
+
+// Rows searched at a time. A match of a pattern that can span rows is cut
+// off if it runs on past the window of rows after the one it starts in.
+const SEARCH_ROWS: usize = 1024;
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Cursor {
     pub x: usize,
     pub y: usize,
 }
 
+// How a range of cells is drawn, either in a style of its own or with
+// their style inverted, as a selection is.
 #[derive(Clone)]
-pub struct Cell {
-    grapheme: String,
-    width: usize,
-    style: Style,
+pub enum Highlight {
+    Style(Style),
+    Inverted,
 }
 
+// A line of the document split into graphemes, which is made when it is
+// needed rather than kept. Styling isn't part of it but is given as spans
+// of cells when it is drawn.
 #[derive(Clone)]
 pub struct Row {
-    cells: Vec<Cell>,
+    text: String,
+    // the offset in the text and the width of each grapheme
+    cells: Vec<(usize, usize)>,
 }
 
-impl Row {
-    fn new() -> Row {
-        Row { cells: vec![] }
-    }
-
-    fn insert_str(&mut self, position: usize, s: &str) {
-        self.cells.splice(position..position, cells(s).cells);
-    }
-
-    fn split_at(&self, position: usize) -> (Row, Row) {
-        let (left, right) = self.cells.split_at(position);
-        (
-            Row {
-                cells: left.to_vec(),
-            },
-            Row {
-                cells: right.to_vec(),
-            },
-        )
+fn text_style() -> Style {
+    Style {
+        foreground: 7,
+        background: 234,
+        decoration: vec![],
     }
+}
 
-    fn append(&mut self, row: Row) {
-        self.cells.extend(row.cells);
-    }
+impl Row {
+    pub fn new(text: String) -> Row {
+        let cells = text
+            .grapheme_indices(false)
+            .map(|(i, g)| (i, if g == "\t" { 4 } else { g.width() }))
+            .collect();
 
-    fn remove(&mut self, position: usize) {
-        self.cells.remove(position);
+        Row { text, cells }
     }
 
-    fn as_string(&self) -> String {
-        let mut line = String::new();
-
-        for cell in self.cells.iter() {
-            line = line + &cell.grapheme;
+    // Returns the offset in the text of the grapheme at `x`, or the length
+    // of the text for the end of the row.
+    fn offset(&self, x: usize) -> usize {
+        match self.cells.get(x) {
+            Some((offset, _)) => *offset,
+            None => self.text.len(),
         }
+    }
 
-        line
+    pub fn grapheme(&self, x: usize) -> &str {
+        &self.text[self.offset(x)..self.offset(x + 1)]
     }
 
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.cells.len()
     }
 
-    pub fn split(&self, max_width: usize, end: &str) -> Vec<String> {
-        let mut display_lines: Vec<String> = vec![];
-        let mut line = String::new();
-        let mut width = 0;
-        let mut style = &Style {
-            foreground: 7,
-            background: 234,
-            decoration: vec![],
-        };
-
-        line.push_str(&styled(style, &String::new()));
-
-        for cell in self.cells.iter() {
-            if &cell.style != style {
-                style = &cell.style;
-                line.push_str(&tabbed(&styled(&style, &cell.grapheme)));
-            } else {
-                line.push_str(&tabbed(&cell.grapheme));
+    // Returns the display line and column each cell is drawn at when the row
+    // is wrapped to `max_width` columns, followed by the position just past
+    // the last cell. Cells too wide for the rest of a line start the next.
+    pub fn layout(&self, max_width: usize) -> Vec<(usize, usize)> {
+        let mut positions = Vec::with_capacity(self.cells.len() + 1);
+        let mut line = 0;
+        let mut col = 0;
+
+        for (_, width) in self.cells.iter() {
+            if col > 0 && col + width > max_width {
+                line += 1;
+                col = 0;
             }
 
-            if width + cell.width < max_width {
-                width += cell.width;
-            } else {
-                display_lines.push(line);
-                line = String::new();
-                width = 0;
-            }
+            positions.push((line, col));
+            col += width;
         }
 
-        if width < max_width {
-            line = line + end;
+        if col >= max_width {
+            line += 1;
+            col = 0;
         }
 
-        display_lines.push(line);
+        positions.push((line, col));
 
-        display_lines
+        positions
     }
 
-    pub fn match_indices(&self, pattern: &str) -> Vec<usize> {
-        if pattern.len() > self.cells.len() {
-            return vec![];
+    // Splits the row into display lines of `max_width` columns, padded to
+    // fill them. Cells in the ranges of `highlights` are drawn as the last
+    // range covering them says.
+    pub fn split(&self, max_width: usize, highlights: &[(usize, usize, Highlight)]) -> Vec<Line> {
+        let layout = self.layout(max_width);
+        let mut display_lines: Vec<Line> = vec![];
+        let mut line: Line = vec![];
+
+        for (i, (cell_line, _)) in layout[..self.cells.len()].iter().enumerate() {
+            if *cell_line > display_lines.len() {
+                fill(&mut line, &text_style(), max_width);
+                display_lines.push(line);
+                line = vec![];
+            }
+
+            let highlight = highlights
+                .iter()
+                .rev()
+                .find(|(start, end, _)| i >= *start && i < *end);
+
+            let style = match highlight {
+                Some((_, _, Highlight::Style(style))) => style.clone(),
+                Some((_, _, Highlight::Inverted)) => text_style().inverted(),
+                None => text_style(),
+            };
+
+            push(&mut line, &style, self.grapheme(i));
         }
 
-        let mut matches = vec![];
-        let pattern_graphemes: Vec<&str> = pattern.graphemes(false).collect();
-
-        for i in 0..self.cells.len() - pattern_graphemes.len() {
-            let mut does_match = true;
-            for j in 0..pattern_graphemes.len() {
-                if self.cells[i + j].grapheme != pattern_graphemes[j] {
-                    does_match = false;
-                    break;
-                }
-            }
-            if does_match {
-                matches.push(i);
-            }
+        if layout[self.cells.len()].0 > display_lines.len() {
+            fill(&mut line, &text_style(), max_width);
+            display_lines.push(line);
+            line = vec![];
         }
 
-        matches
-    }
-}
+        fill(&mut line, &text_style(), max_width);
+        display_lines.push(line);
 
-pub fn cells(line: &str) -> Row {
-    Row {
-        cells: line
-            .graphemes(false)
-            .map(|g| {
-                let grapheme = g.to_string();
-
-                if g == String::from("\t") {
-                    Cell {
-                        grapheme: grapheme,
-                        width: 4,
-                        style: Style {
-                            foreground: 7,
-                            background: 234,
-                            decoration: vec![],
-                        },
-                    }
-                } else {
-                    Cell {
-                        grapheme: grapheme,
-                        width: g.width(),
-                        style: Style {
-                            foreground: 7,
-                            background: 234,
-                            decoration: vec![],
-                        },
-                    }
-                }
-            })
-            .collect(),
+        display_lines
     }
 }
 
 pub struct Document {
-    pub rows: Vec<Row>,
+    // The lines of the text, each ending with '\n'. A document with no
+    // lines at all is empty, unlike one with a single empty line.
+    text: Text,
     pub cursor: Cursor,
     pub filename: Option<String>,
+    // The canonical form of the filename, which tells whether two
+    // documents are the same file.
+    path: Option<PathBuf>,
+    pub encoding: Encoding,
+    pub readonly: bool,
+    anchor: Option<Cursor>,
+    history: History,
+    // The matches of the search in progress in order, which are highlighted
+    // and moved between, and the one that is drawn emphasized.
+    pub matches: Vec<(Cursor, Cursor)>,
+    pub current_match: Option<usize>,
+    // The first row changed since the view last caught up with the text,
+    // and how many rows at the end haven't changed since.
+    changed_rows: Option<(usize, usize)>,
+    // Counts the changes to the text, so what is worked out from it can
+    // tell when it is out of date.
+    revision: usize,
 }
 
 impl Document {
     pub fn blank() -> Document {
         Document {
-            rows: vec![],
+            text: Text::new(String::new()),
             cursor: Cursor { x: 0, y: 0 },
             filename: None,
+            path: None,
+            encoding: config::get().encoding,
+            readonly: config::get().readonly,
+            anchor: None,
+            history: History::new(),
+            matches: vec![],
+            current_match: None,
+            changed_rows: None,
+            revision: 0,
         }
     }
 
     pub fn open(&mut self, filename: String) -> io::Result<()> {
         let mut contents = String::new();
 
+        path::check_readable(&filename)?;
+
         if Path::new(&filename).exists() {
-            let mut file = File::open(filename.clone())?;
-            file.read_to_string(&mut contents)?;
+            let bytes = fs::read(&filename).map_err(|e| path::describe(&filename, e))?;
+            contents = self
+                .encoding
+                .decode(&bytes)
+                .map_err(|e| path::describe(&filename, e))?;
         }
 
-        self.rows = contents.lines().map(cells).collect();
-        self.cursor = Cursor { x: 0, y: 0 };
+        self.load(contents);
+        self.path = Some(path::canonical(&filename));
         self.filename = Some(filename);
 
         Ok(())
     }
 
+    // Reads an unnamed document, such as text piped to the editor.
+    pub fn from_reader(mut reader: impl Read) -> io::Result<Document> {
+        let mut document = Document::blank();
+        let mut bytes = vec![];
+
+        reader.read_to_end(&mut bytes)?;
+        document.load(document.encoding.decode(&bytes)?);
+
+        Ok(document)
+    }
+
+    fn load(&mut self, mut contents: String) {
+        // lines are read as `str::lines` splits them, and the text kept as
+        // it is when it already has that form
+        if contents.contains("\r\n") {
+            contents = contents.replace("\r\n", "\n");
+        }
+
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+
+        let removed = self.line_count();
+        self.text = Text::new(contents);
+        self.rows_replaced(0, removed, self.line_count());
+        self.cursor = Cursor { x: 0, y: 0 };
+        self.anchor = None;
+        self.history = History::new();
+    }
+
     pub fn name(&self) -> String {
         self.filename.clone().unwrap_or("New File".to_string())
     }
 
-    pub fn insert(&mut self, c: &String) {
-        assert!(c.len() == 1);
+    pub fn line_count(&self) -> usize {
+        self.text.newlines()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.len() == 0
+    }
+
+    // Returns line `y` without its newline.
+    pub fn line(&self, y: usize) -> String {
+        let start = self.text.after_newline(y);
+        let end = self.text.after_newline(y + 1);
+
+        self.text.slice(start, end.saturating_sub(1).max(start))
+    }
+
+    pub fn row(&self, y: usize) -> Row {
+        Row::new(self.line(y))
+    }
+
+    // Returns rows `start` to `end`, each followed by a newline.
+    pub fn rows_text(&self, start: usize, end: usize) -> String {
+        self.text
+            .slice(self.text.after_newline(start), self.text.after_newline(end))
+    }
+
+    // Notes that `removed` rows from row `y` were replaced by `inserted`
+    // rows.
+    fn rows_replaced(&mut self, y: usize, removed: usize, inserted: usize) {
+        let before = self.line_count() + removed - inserted;
+        let unchanged = before - (y + removed);
+
+        self.revision += 1;
+        self.changed_rows = Some(match self.changed_rows {
+            Some((first, rest)) => (first.min(y), rest.min(unchanged)),
+            None => (y, unchanged),
+        });
+    }
+
+    // Returns the first row changed since the last call and how many rows
+    // at the end are unchanged, or None if nothing changed.
+    pub fn take_changed_rows(&mut self) -> Option<(usize, usize)> {
+        self.changed_rows.take()
+    }
+
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    // Returns the length of line `y` in graphemes.
+    pub fn line_len(&self, y: usize) -> usize {
+        self.line(y).graphemes(false).count()
+    }
+
+    // Returns the offset in the text of `position`.
+    fn offset(&self, position: Cursor) -> usize {
+        self.text.after_newline(position.y) + self.row(position.y).offset(position.x)
+    }
 
-        if self.rows.len() == 0 {
-            self.rows.push(Row::new());
+    // Returns the position of the grapheme holding the byte at `offset`, or
+    // of the one after it when `offset` falls inside a grapheme.
+    fn position(&self, offset: usize) -> Cursor {
+        let y = self.text.line_at(offset);
+        let column = offset - self.text.after_newline(y);
+        let row = self.row(y);
+
+        Cursor {
+            x: row.cells.partition_point(|(start, _)| *start < column),
+            y,
+        }
+    }
+
+    // Replaces the `len` bytes from `offset` with `text` without touching the
+    // cursor or history and returns the text replaced.
+    fn splice(&mut self, offset: usize, len: usize, text: &str) -> String {
+        if self.is_empty() {
+            self.text.insert(0, "\n");
+            self.rows_replaced(0, 0, 1);
         }
 
-        self.rows[self.cursor.y].insert_str(self.cursor.x, &c);
-        self.cursor.x += 1;
+        let y = self.text.line_at(offset);
+        let removed = self.text.slice(offset, offset + len);
+
+        self.text.remove(offset, offset + len);
+        self.text.insert(offset, text);
+        self.rows_replaced(
+            y,
+            removed.matches('\n').count() + 1,
+            text.matches('\n').count() + 1,
+        );
+
+        removed
     }
 
-    pub fn insert_line(&mut self) {
-        if self.rows.len() == 0 {
+    // Inserts `text` at `at` without touching the cursor or history and
+    // returns the position just past the inserted text. That is worked out
+    // from the text it ends up in, as it may join the graphemes around it.
+    fn insert_text(&mut self, at: Cursor, text: &str) -> Cursor {
+        let offset = self.offset(at);
+
+        self.splice(offset, 0, text);
+        self.position(offset + text.len())
+    }
+
+    // Removes the text between `start` and `end` without touching the cursor
+    // or history and returns it.
+    fn remove_text(&mut self, start: Cursor, end: Cursor) -> String {
+        let start = self.offset(start);
+        let end = self.offset(end);
+
+        self.splice(start, end - start, "")
+    }
+
+    // Returns the text between `start` and `end` with lines joined by '\n'.
+    pub fn text(&self, start: Cursor, end: Cursor) -> String {
+        self.text.slice(self.offset(start), self.offset(end))
+    }
+
+    // Replaces the text between `start` and `end` with `text`, moves the
+    // cursor to the end of the new text and records the edit for undo.
+    fn replace(&mut self, kind: Kind, start: Cursor, end: Cursor, text: &str) {
+        if self.readonly {
             return;
         }
 
-        let row = self.rows.remove(self.cursor.y);
-        let (first, last) = row.split_at(self.cursor.x);
+        let before = self.cursor;
+        let at = self.offset(start);
 
-        self.rows.insert(self.cursor.y, last);
-        self.rows.insert(self.cursor.y, first);
+        let removed = if start == end {
+            String::new()
+        } else {
+            self.remove_text(start, end)
+        };
 
-        self.cursor.y += 1;
-        self.cursor.x = 0;
+        self.cursor = self.insert_text(start, text);
+
+        let edit = Edit {
+            at,
+            removed,
+            inserted: text.to_string(),
+        };
+
+        self.history.record(kind, edit, before, self.cursor);
+    }
+
+    pub fn insert(&mut self, c: &str) {
+        let (mut start, end) = self.take_selection();
+        let mut text = c.to_string();
+
+        // Combining marks and other grapheme extenders belong in the cell
+        // before the cursor, so that cell is replaced along with the insert.
+        if start == end && start.x > 0 {
+            let row = self.row(start.y);
+            let prev = row.grapheme(start.x - 1);
+            let joined = format!("{}{}", prev, c);
+
+            if joined.graphemes(false).next() != Some(prev) {
+                start.x -= 1;
+                text = joined;
+            }
+        }
+
+        self.replace(Kind::Insert, start, end, &text);
+    }
+
+    pub fn insert_line(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (start, end) = self.take_selection();
+        self.replace(Kind::Other, start, end, "\n");
     }
 
     pub fn delete_next(&mut self) {
-        if self.rows.len() == 0 {
+        if self.is_empty() {
             return;
         }
 
-        if !(self.on_last_line() && self.on_last_char()) {
-            self.right();
-            self.delete_prev();
+        if self.delete_selection() {
+            return;
+        }
+
+        if self.on_last_line() && self.on_last_char() {
+            return;
         }
+
+        let end = if self.on_last_char() {
+            Cursor {
+                x: 0,
+                y: self.cursor.y + 1,
+            }
+        } else {
+            Cursor {
+                x: self.cursor.x + 1,
+                y: self.cursor.y,
+            }
+        };
+
+        self.replace(Kind::Delete, self.cursor, end, "");
     }
 
     pub fn delete_prev(&mut self) {
-        if self.on_first_char() && !self.on_first_line() {
-            let prev = self.rows.remove(self.cursor.y);
+        if self.delete_selection() {
+            return;
+        }
 
-            self.cursor.y -= 1;
-            self.cursor.x = self.current_line_len();
+        if self.on_first_char() && self.on_first_line() {
+            return;
+        }
 
-            self.rows[self.cursor.y].append(prev);
-        } else if !self.on_first_char() {
-            self.cursor.x -= 1;
-            self.rows[self.cursor.y].remove(self.cursor.x);
+        let start = if self.on_first_char() {
+            Cursor {
+                x: self.line_len(self.cursor.y - 1),
+                y: self.cursor.y - 1,
+            }
+        } else {
+            Cursor {
+                x: self.cursor.x - 1,
+                y: self.cursor.y,
+            }
+        };
+
+        self.replace(Kind::Backspace, start, self.cursor, "");
+    }
+
+    // Deletes back to the start of the current or previous word.
+    pub fn delete_word_prev(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+
+        let end = self.cursor;
+        self.word_left();
+        let start = self.cursor;
+        self.cursor = end;
+
+        if start != end {
+            self.replace(Kind::Other, start, end, "");
+        }
+    }
+
+    // Deletes up to the end of the current or next word.
+    pub fn delete_word_next(&mut self) {
+        if self.is_empty() || self.delete_selection() {
+            return;
+        }
+
+        let start = self.cursor;
+        self.word_right();
+        let end = self.cursor;
+        self.cursor = start;
+
+        if start != end {
+            self.replace(Kind::Other, start, end, "");
         }
     }
 
     pub fn tab(&mut self) {
-        for _ in 0..4 {
-            self.insert(&" ".to_string());
+        let (start, end) = self.take_selection();
+        self.replace(Kind::Insert, start, end, "    ");
+    }
+
+    // Replaces the selection, or inserts at the cursor, with `text` as a
+    // single undo step.
+    pub fn paste(&mut self, text: &str) {
+        let (start, end) = self.take_selection();
+        self.replace(Kind::Other, start, end, text);
+    }
+
+    // Starts a selection at the cursor unless one is already in progress.
+    pub fn select(&mut self) {
+        if self.anchor.is_none() {
+            self.anchor = Some(self.cursor);
+        }
+    }
+
+    pub fn deselect(&mut self) {
+        self.anchor = None;
+    }
+
+    pub fn select_all(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        self.anchor = Some(Cursor { x: 0, y: 0 });
+        self.cursor.y = self.line_count() - 1;
+        self.end_of_line();
+    }
+
+    // Returns the selected range ordered from start to end, or None if
+    // nothing is selected.
+    pub fn selection(&self) -> Option<(Cursor, Cursor)> {
+        let anchor = self.anchor?;
+
+        if anchor == self.cursor {
+            None
+        } else if (anchor.y, anchor.x) < (self.cursor.y, self.cursor.x) {
+            Some((anchor, self.cursor))
+        } else {
+            Some((self.cursor, anchor))
         }
     }
 
-    pub fn set_filename(&mut self, filename: String) {
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        Some(self.text(start, end))
+    }
+
+    // Removes the selected text and returns it.
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.selected_text()?;
+        self.delete_selection();
+        Some(text)
+    }
+
+    // Clears the selection and returns the range it covered, or an empty
+    // range at the cursor if nothing was selected.
+    fn take_selection(&mut self) -> (Cursor, Cursor) {
+        let range = self.selection().unwrap_or((self.cursor, self.cursor));
+        self.anchor = None;
+        range
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.take_selection() {
+            (start, end) if start != end => {
+                self.replace(Kind::Other, start, end, "");
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn undo(&mut self) {
+        self.anchor = None;
+
+        if let Some(change) = self.history.undo() {
+            for edit in change.edits.iter().rev() {
+                self.splice(edit.at, edit.inserted.len(), &edit.removed);
+            }
+
+            self.cursor = change.before;
+        }
+    }
+
+    pub fn redo(&mut self) {
+        self.anchor = None;
+
+        if let Some(change) = self.history.redo() {
+            for edit in change.edits.iter() {
+                self.splice(edit.at, edit.removed.len(), &edit.inserted);
+            }
+
+            self.cursor = change.after;
+        }
+    }
+
+    // Ends the current undo group so the next edit starts a new one.
+    pub fn seal_history(&mut self) {
+        self.history.seal();
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // Saves the document as `filename`, which it goes by from then on only
+    // if the save succeeds.
+    pub fn save_as(&mut self, filename: String) -> io::Result<()> {
+        let previous = (self.filename.take(), self.path.take());
+
+        self.path = Some(path::canonical(&filename));
         self.filename = Some(filename);
+
+        let saved = self.save();
+
+        if saved.is_err() {
+            (self.filename, self.path) = previous;
+        }
+
+        saved
     }
 
-    pub fn save(&self) -> std::io::Result<()> {
+    pub fn save(&mut self) -> std::io::Result<()> {
         if let Some(filename) = &self.filename {
-            let mut buffer = File::create(filename)?;
-
-            for row in &self.rows {
-                buffer.write_all(row.as_string().as_bytes())?;
-                buffer.write_all(&[b'\n'])?;
+            if self.readonly {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} is open read-only", filename),
+                ));
             }
+
+            let text: String = self.text.chunks().collect();
+
+            // encoded first so text that can't be encoded doesn't leave the
+            // file cut short
+            let bytes = self.encoding.encode(&text)?;
+            File::create(filename)?.write_all(&bytes)?;
+
+            self.history.mark_saved();
         }
 
         Ok(())
     }
 
+    // Returns true if the document has changed since it was opened or saved.
+    pub fn is_dirty(&self) -> bool {
+        self.history.modified()
+    }
+
     pub fn on_first_line(&self) -> bool {
         self.cursor.y == 0
     }
 
     pub fn on_last_line(&self) -> bool {
-        self.cursor.y == self.rows.len() - 1
+        self.cursor.y == self.line_count() - 1
     }
 
     fn on_first_char(&self) -> bool {
@@ -292,42 +685,24 @@ impl Document {
     }
 
     pub fn current_line_len(&self) -> usize {
-        if self.rows.len() == 0 {
+        if self.is_empty() {
             return 0;
         }
 
-        self.rows[self.cursor.y].len()
-    }
-
-    pub fn up(&mut self) {
-        if self.on_first_line() {
-            self.start_of_line();
-        } else {
-            self.cursor.y -= 1;
-            self.cursor.x = std::cmp::min(self.cursor.x, self.current_line_len());
-        }
-    }
-
-    pub fn down(&mut self) {
-        if self.on_last_line() {
-            self.end_of_line();
-        } else {
-            self.cursor.y += 1;
-            self.cursor.x = std::cmp::min(self.cursor.x, self.current_line_len());
-        }
+        self.line_len(self.cursor.y)
     }
 
     pub fn left(&mut self) {
         if self.on_first_char() && !self.on_first_line() {
             self.cursor.y -= 1;
-            self.cursor.x = self.rows[self.cursor.y].len();
+            self.cursor.x = self.line_len(self.cursor.y);
         } else if !self.on_first_char() {
             self.cursor.x -= 1;
         }
     }
 
     pub fn right(&mut self) {
-        if self.rows.len() == 0 {
+        if self.is_empty() {
             return;
         }
 
@@ -339,6 +714,42 @@ impl Document {
         }
     }
 
+    // Moves to the start of the current or previous word.
+    pub fn word_left(&mut self) {
+        if self.on_first_char() {
+            self.left();
+            return;
+        }
+
+        let row = self.row(self.cursor.y);
+
+        while self.cursor.x > 0 && !is_word(row.grapheme(self.cursor.x - 1)) {
+            self.cursor.x -= 1;
+        }
+
+        while self.cursor.x > 0 && is_word(row.grapheme(self.cursor.x - 1)) {
+            self.cursor.x -= 1;
+        }
+    }
+
+    // Moves to the end of the current or next word.
+    pub fn word_right(&mut self) {
+        if self.on_last_char() {
+            self.right();
+            return;
+        }
+
+        let row = self.row(self.cursor.y);
+
+        while self.cursor.x < row.len() && !is_word(row.grapheme(self.cursor.x)) {
+            self.cursor.x += 1;
+        }
+
+        while self.cursor.x < row.len() && is_word(row.grapheme(self.cursor.x)) {
+            self.cursor.x += 1;
+        }
+    }
+
     pub fn end_of_line(&mut self) {
         self.cursor.x = self.current_line_len();
     }
@@ -347,47 +758,245 @@ impl Document {
         self.cursor.x = 0;
     }
 
-    pub fn find_next(&mut self, text: String) {
-        let mut matches: Vec<(usize, usize)> = vec![];
+    // Moves to `line` and `column`, counted from 1, or as near to them as
+    // the document allows.
+    pub fn go_to(&mut self, line: usize, column: usize) {
+        self.anchor = None;
+        self.cursor.y = line
+            .saturating_sub(1)
+            .min(self.line_count().saturating_sub(1));
+        self.cursor.x = column.saturating_sub(1).min(self.current_line_len());
+    }
 
-        for i in 0..self.rows.len() {
-            for m in self.rows[i].match_indices(&text) {
-                matches.push((m, i));
+    // Calls `found` with the start and end of each match of `pattern`, the
+    // text searched and the offset of the match in it. Rows are searched a
+    // window at a time as text joined by '\n', and only the ends of the
+    // matches are turned into positions. Matches that start or end inside a
+    // grapheme are widened to the whole grapheme.
+    fn search(&self, pattern: &Pattern, mut found: impl FnMut(Cursor, Cursor, &str, usize)) {
+        let count = self.line_count();
+        // patterns that can match a newline also see the window of rows
+        // after, so matches running on into it are found whole
+        let ahead = if pattern.spans_lines { SEARCH_ROWS } else { 0 };
+        // where to go on searching from, in the window being searched
+        let mut from = 0;
+        // the text of the rows from the window being searched on, and the
+        // first row not among them
+        let mut text = String::new();
+        let mut loaded = 0;
+
+        for y in (0..count).step_by(SEARCH_ROWS) {
+            let end = (y + SEARCH_ROWS + ahead).min(count);
+
+            if loaded < end {
+                text.push_str(&self.rows_text(loaded, end));
+                loaded = end;
+
+                // the last row isn't followed by a newline to match
+                if end == count {
+                    text.pop();
+                }
             }
-        }
-
-        if matches.len() > 0 {
-            let mut next = matches[0];
 
-            for m in matches {
-                if m.1 == self.cursor.y && m.0 > self.cursor.x {
-                    next = m;
-                    break;
-                } else if m.1 > self.cursor.y {
-                    next = m;
-                    break;
+            let mut starts = vec![0];
+            starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+            // matches starting in the rows after the window are left to the
+            // next, but at the end of the text one can start past the last
+            // byte
+            let own = starts.get(SEARCH_ROWS).copied().unwrap_or(text.len() + 1);
+
+            // the row last split into graphemes and where each starts, or
+            // None when each byte of it is a grapheme, as in ASCII rows
+            let mut graphemes: (usize, Option<Vec<usize>>) = (usize::MAX, None);
+            let mut position = |offset: usize, round_up: bool| {
+                let row = starts.partition_point(|start| *start <= offset) - 1;
+                let row_end = starts.get(row + 1).map_or(text.len(), |start| start - 1);
+                let row_text = &text[starts[row]..row_end];
+
+                if graphemes.0 != row {
+                    graphemes.0 = row;
+                    graphemes.1 = match row_text.is_ascii() {
+                        true => None,
+                        false => Some(
+                            row_text
+                                .grapheme_indices(false)
+                                .map(|(i, _)| starts[row] + i)
+                                .collect(),
+                        ),
+                    };
                 }
+
+                let x = match (&graphemes.1, round_up, offset < row_end) {
+                    (None, _, _) => offset - starts[row],
+                    (Some(offsets), true, _) => offsets.partition_point(|o| *o < offset),
+                    (Some(offsets), false, true) => offsets.partition_point(|o| *o <= offset) - 1,
+                    (Some(offsets), false, false) => offsets.len(),
+                };
+
+                Cursor { x, y: y + row }
+            };
+
+            while from <= text.len() {
+                let m = match pattern.regex.find_at(&text, from) {
+                    Some(m) if m.start() < own => m,
+                    _ => break,
+                };
+
+                let start_at = position(m.start(), false);
+                let end_at = match m.is_empty() {
+                    true => start_at,
+                    false => position(m.end(), true),
+                };
+
+                found(start_at, end_at, &text, m.start());
+
+                // an empty match would be found again at the same place
+                from = match m.is_empty() {
+                    true => m.end() + text[m.end()..].chars().next().map_or(1, char::len_utf8),
+                    false => m.end(),
+                };
             }
 
-            self.cursor.x = next.0;
-            self.cursor.y = next.1;
+            from = from.saturating_sub(own);
+            text.drain(..own.min(text.len()));
         }
     }
 
-    pub fn cursor_display_x(&self) -> usize {
-        let mut display_len = 0;
+    pub fn find_all(&self, pattern: &Pattern) -> Vec<(Cursor, Cursor)> {
+        let mut matches = vec![];
+
+        self.search(pattern, |start, end, _, _| matches.push((start, end)));
 
-        for i in 0..self.cursor.x {
-            display_len += self.rows[self.cursor.y].cells[i].width;
+        matches
+    }
+
+    // Returns every match of `pattern` with the text to replace it with.
+    // When `expand` is set, `$n` in `replacement` stands for group n of the
+    // match.
+    pub fn replacements(
+        &self,
+        pattern: &Pattern,
+        replacement: &str,
+        expand: bool,
+    ) -> Vec<(Cursor, Cursor, String)> {
+        let mut replacements = vec![];
+
+        self.search(pattern, |start, end, text, at| {
+            let captures = pattern.regex.captures_at(text, at);
+
+            let text = match (expand, captures) {
+                (true, Some(captures)) => pattern::expand(&captures, replacement),
+                _ => replacement.to_string(),
+            };
+
+            replacements.push((start, end, text));
+        });
+
+        replacements
+    }
+
+    // Replaces the text between `start` and `end` and returns the position
+    // of the end of the new text, where the cursor is moved to.
+    pub fn replace_range(&mut self, start: Cursor, end: Cursor, text: &str) -> Cursor {
+        self.anchor = None;
+        self.replace(Kind::Other, start, end, text);
+        self.cursor
+    }
+
+    // Makes every replacement, which must be in order and not overlap, as a
+    // single change for undo, and moves the cursor to the first of them.
+    pub fn replace_all(&mut self, replacements: &[(Cursor, Cursor, String)]) {
+        if self.readonly || replacements.is_empty() {
+            return;
+        }
+
+        let before = self.cursor;
+        let mut edits = vec![];
+
+        // replacing from the end leaves the earlier positions unchanged
+        for (start, end, text) in replacements.iter().rev() {
+            let at = self.offset(*start);
+            let removed = if start == end {
+                String::new()
+            } else {
+                self.remove_text(*start, *end)
+            };
+
+            self.insert_text(*start, text);
+
+            edits.push(Edit {
+                at,
+                removed,
+                inserted: text.clone(),
+            });
         }
 
-        display_len
+        self.anchor = None;
+        self.cursor = replacements[0].0;
+        self.history.record_all(edits, before, self.cursor);
+    }
+
+    // Moves the cursor to the start of the first of the matches at or after
+    // `from`, going back to the first match in the document if there are
+    // none after it. Returns whether it went back, or None if there are no
+    // matches.
+    pub fn find_from(&mut self, from: Cursor) -> Option<bool> {
+        let after = self
+            .matches
+            .partition_point(|(start, _)| (start.y, start.x) < (from.y, from.x));
+
+        let (next, wrapped) = match self.matches.get(after) {
+            Some((start, _)) => (*start, false),
+            None => (self.matches.first()?.0, true),
+        };
+
+        self.anchor = None;
+        self.cursor = next;
+
+        Some(wrapped)
+    }
+
+    // Moves the cursor to the first match after it, as `find_from` does.
+    pub fn find_next(&mut self) -> Option<bool> {
+        let from = Cursor {
+            x: self.cursor.x + 1,
+            y: self.cursor.y,
+        };
+
+        self.find_from(from)
+    }
+
+    // Moves the cursor to the start of the last match before it, going on to
+    // the last match in the document if there are none before it.
+    pub fn find_prev(&mut self) -> Option<bool> {
+        let cursor = self.cursor;
+        let before = self
+            .matches
+            .partition_point(|(start, _)| (start.y, start.x) < (cursor.y, cursor.x));
+
+        let (prev, wrapped) = match before.checked_sub(1) {
+            Some(i) => (self.matches[i].0, false),
+            None => (self.matches.last()?.0, true),
+        };
+
+        self.anchor = None;
+        self.cursor = prev;
+
+        Some(wrapped)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
+    use crate::document::Cursor;
     use crate::document::Document;
+    use crate::document::SEARCH_ROWS;
+    use crate::fixture::Fixture;
+    use crate::pattern::Flags;
+    use crate::pattern::Pattern;
 
     #[test]
     fn current_line_len() {
@@ -404,4 +1013,402 @@ mod tests {
         document.delete_prev();
         assert_eq!(document.current_line_len(), 0);
     }
+
+    fn contents(document: &Document) -> Vec<String> {
+        (0..document.line_count())
+            .map(|y| document.line(y))
+            .collect()
+    }
+
+    #[test]
+    fn undo_groups_typing() {
+        let mut document = Document::blank();
+
+        for c in ["a", "b", "c"] {
+            document.insert(&String::from(c));
+        }
+        document.insert_line();
+        document.insert(&String::from("d"));
+
+        assert_eq!(contents(&document), vec!["abc", "d"]);
+
+        // Typing after the line break is undone first
+        document.undo();
+        assert_eq!(contents(&document), vec!["abc", ""]);
+
+        // The line break is its own step
+        document.undo();
+        assert_eq!(contents(&document), vec!["abc"]);
+        assert_eq!(document.cursor.x, 3);
+
+        // The run of typing is undone at once
+        document.undo();
+        assert_eq!(contents(&document), vec![""]);
+        assert_eq!(document.cursor.x, 0);
+
+        // Nothing left to undo
+        document.undo();
+        assert_eq!(contents(&document), vec![""]);
+    }
+
+    #[test]
+    fn redo_restores_undone_edits() {
+        let mut document = Document::blank();
+
+        document.insert(&String::from("a"));
+        document.insert_line();
+        document.insert(&String::from("b"));
+        document.delete_prev();
+        document.delete_prev();
+
+        assert_eq!(contents(&document), vec!["a"]);
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["a", "b"]);
+        assert_eq!(document.cursor.y, 1);
+        assert_eq!(document.cursor.x, 1);
+
+        document.redo();
+        assert_eq!(contents(&document), vec!["a"]);
+        assert_eq!(document.cursor.y, 0);
+        assert_eq!(document.cursor.x, 1);
+
+        // A new edit clears the redo stack
+        document.undo();
+        document.insert(&String::from("c"));
+        document.redo();
+        assert_eq!(contents(&document), vec!["a", "bc"]);
+    }
+
+    #[test]
+    fn delete_next_joins_lines() {
+        let mut document = Document::blank();
+
+        document.insert(&String::from("a"));
+        document.insert_line();
+        document.insert(&String::from("b"));
+        document.cursor = Cursor { x: 1, y: 0 };
+        document.delete_next();
+
+        assert_eq!(contents(&document), vec!["ab"]);
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["a", "b"]);
+        assert_eq!(document.cursor.y, 0);
+        assert_eq!(document.cursor.x, 1);
+    }
+
+    #[test]
+    fn insert_merges_combining_marks() {
+        let mut document = Document::blank();
+
+        document.insert("e");
+        document.insert("\u{301}");
+        document.insert("ß");
+
+        assert_eq!(contents(&document), vec!["e\u{301}ß"]);
+        assert_eq!(document.current_line_len(), 2);
+        assert_eq!(document.cursor.x, 2);
+
+        document.left();
+        document.insert("ぁ");
+        assert_eq!(contents(&document), vec!["e\u{301}ぁß"]);
+        assert_eq!(document.row(0).layout(80)[2], (0, 3));
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["e\u{301}ß"]);
+
+        document.undo();
+        assert_eq!(contents(&document), vec![""]);
+    }
+
+    #[test]
+    fn selection_cut_and_paste() {
+        let mut document = Document::blank();
+
+        document.paste("one\ntwo\nthree");
+        assert_eq!(contents(&document), vec!["one", "two", "three"]);
+
+        // Select from the middle of the first line to the middle of the last
+        document.cursor = Cursor { x: 1, y: 0 };
+        document.select();
+        document.cursor = Cursor { x: 2, y: 2 };
+        assert_eq!(document.selected_text(), Some(String::from("ne\ntwo\nth")));
+
+        let text = document.cut().unwrap();
+        assert_eq!(contents(&document), vec!["oree"]);
+        assert_eq!(document.cursor.x, 1);
+        assert!(document.selection().is_none());
+
+        // Typing replaces the selection
+        document.select_all();
+        document.insert("x");
+        assert_eq!(contents(&document), vec!["x"]);
+
+        document.paste(&text);
+        assert_eq!(contents(&document), vec!["xne", "two", "th"]);
+
+        document.undo();
+        document.undo();
+        assert_eq!(contents(&document), vec!["oree"]);
+    }
+
+    #[test]
+    fn edits_join_graphemes_around_them() {
+        let mut document = Document::blank();
+
+        // A paste starting with a combining mark joins the cell before it
+        document.paste("e");
+        document.paste("\u{301}x");
+        assert_eq!(contents(&document), vec!["e\u{301}x"]);
+        assert_eq!(document.cursor.x, 2);
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["e"]);
+        assert_eq!(document.cursor.x, 1);
+
+        document.redo();
+        assert_eq!(contents(&document), vec!["e\u{301}x"]);
+        assert_eq!(document.cursor.x, 2);
+
+        // Text typed before a combining mark takes it on
+        document.paste("\n\u{301}a");
+        document.cursor = Cursor { x: 0, y: 1 };
+        document.insert("e");
+        assert_eq!(contents(&document), vec!["e\u{301}x", "e\u{301}a"]);
+        assert_eq!(document.cursor.x, 1);
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["e\u{301}x", "\u{301}a"]);
+        assert_eq!((document.cursor.x, document.cursor.y), (0, 1));
+    }
+
+    #[test]
+    fn word_motion() {
+        let mut document = Document::blank();
+
+        document.paste("let x_1 = 10;\nfoo");
+        document.cursor = Cursor { x: 0, y: 0 };
+
+        document.word_right();
+        assert_eq!(document.cursor.x, 3);
+        document.word_right();
+        assert_eq!(document.cursor.x, 7);
+        document.word_right();
+        assert_eq!(document.cursor.x, 12);
+        document.word_right();
+        assert_eq!(document.cursor.x, 13);
+
+        // Wraps to the next line at the end of a line
+        document.word_right();
+        assert_eq!((document.cursor.x, document.cursor.y), (0, 1));
+        document.word_left();
+        assert_eq!((document.cursor.x, document.cursor.y), (13, 0));
+
+        document.word_left();
+        assert_eq!(document.cursor.x, 10);
+        document.word_left();
+        assert_eq!(document.cursor.x, 4);
+    }
+
+    #[test]
+    fn delete_words() {
+        let mut document = Document::blank();
+
+        document.paste("foo bar.baz\nqux");
+        document.cursor = Cursor { x: 11, y: 0 };
+
+        document.delete_word_prev();
+        assert_eq!(contents(&document), vec!["foo bar.", "qux"]);
+
+        document.delete_word_next();
+        assert_eq!(contents(&document), vec!["foo bar.qux"]);
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["foo bar.", "qux"]);
+        assert_eq!(document.cursor.x, 8);
+    }
+
+    #[test]
+    fn reads_unnamed_document() {
+        let document = Document::from_reader("one\ntwo\n".as_bytes()).unwrap();
+
+        assert_eq!(contents(&document), vec!["one", "two"]);
+        assert!(document.filename.is_none());
+        assert!(!document.is_dirty());
+    }
+
+    #[test]
+    fn finds_matches_by_grapheme() {
+        let mut document = Document::blank();
+        let flags = Flags::default();
+
+        document.paste("ぁé x\nfoo e\u{301}x");
+
+        let pattern = Pattern::new("x$\\s+f", flags).unwrap();
+        assert!(
+            document.find_all(&pattern) == vec![(Cursor { x: 3, y: 0 }, Cursor { x: 1, y: 1 })]
+        );
+
+        // a match inside a grapheme covers all of it
+        let pattern = Pattern::new("e", flags).unwrap();
+        assert!(
+            document.find_all(&pattern) == vec![(Cursor { x: 4, y: 1 }, Cursor { x: 5, y: 1 })]
+        );
+
+        document.cursor = Cursor { x: 0, y: 1 };
+        document.matches = document.find_all(&Pattern::new("x", flags).unwrap());
+        document.find_next();
+        assert!(document.cursor == Cursor { x: 5, y: 1 });
+        document.find_next();
+        assert!(document.cursor == Cursor { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn finds_matches_across_windows() {
+        let mut document = Document::blank();
+        let flags = Flags::default();
+        let at = |x, y| Cursor { x, y };
+
+        // the window boundary falls between the two rows of the middle match
+        document.paste(&"x\n".repeat(SEARCH_ROWS - 1));
+        document.paste("ab\ncd\n");
+        document.paste(&"x\n".repeat(SEARCH_ROWS));
+
+        let pattern = Pattern::new("b\\nc", flags).unwrap();
+        assert!(document.find_all(&pattern) == vec![(at(1, SEARCH_ROWS - 1), at(1, SEARCH_ROWS))]);
+
+        // empty matches at the start of each row are found once
+        let pattern = Pattern::new("^", flags).unwrap();
+        assert_eq!(document.find_all(&pattern).len(), document.line_count());
+        let pattern = Pattern::new("^\\s*", flags).unwrap();
+        assert_eq!(document.find_all(&pattern).len(), document.line_count());
+
+        let pattern = Pattern::new("(d)\\n(x)", flags).unwrap();
+        let replacements = document.replacements(&pattern, "$2$1", true);
+        assert!(replacements.len() == 1 && replacements[0].2 == "xd");
+    }
+
+    #[test]
+    fn finds_matches_both_ways() {
+        let mut document = Document::blank();
+        let pattern = Pattern::new("ab", Flags::default()).unwrap();
+
+        document.paste(
+            "ab ab
+ab",
+        );
+
+        document.matches = document.find_all(&pattern);
+        assert_eq!(document.find_from(Cursor { x: 3, y: 0 }), Some(false));
+        assert!(document.cursor == Cursor { x: 3, y: 0 });
+        assert_eq!(document.find_prev(), Some(false));
+        assert!(document.cursor == Cursor { x: 0, y: 0 });
+        assert_eq!(document.find_prev(), Some(true));
+        assert!(document.cursor == Cursor { x: 0, y: 1 });
+        assert_eq!(document.find_next(), Some(true));
+        assert!(document.cursor == Cursor { x: 0, y: 0 });
+
+        let pattern = Pattern::new("c", Flags::default()).unwrap();
+        document.matches = document.find_all(&pattern);
+        assert_eq!(document.find_prev(), None);
+        assert!(document.cursor == Cursor { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn replaces_all_as_one_change() {
+        let mut document = Document::blank();
+        let flags = Flags::default();
+
+        document.paste("a=1\nbb=22");
+        document.cursor = Cursor { x: 0, y: 0 };
+
+        let pattern = Pattern::new("(\\w+)=(\\d+)", flags).unwrap();
+        let replacements = document.replacements(&pattern, "$2: $1\\n", true);
+        document.replace_all(&replacements);
+        assert_eq!(contents(&document), vec!["1: a", "", "22: bb", ""]);
+
+        document.undo();
+        assert_eq!(contents(&document), vec!["a=1", "bb=22"]);
+        document.redo();
+        assert_eq!(contents(&document), vec!["1: a", "", "22: bb", ""]);
+
+        let pattern = Pattern::new("b", flags).unwrap();
+        document.replace_all(&document.replacements(&pattern, "$1", false));
+        assert_eq!(contents(&document), vec!["1: a", "", "22: $1$1", ""]);
+    }
+
+    #[test]
+    fn layout_wraps_wide_cells() {
+        let mut document = Document::blank();
+
+        document.paste("abぁcd");
+        let row = document.row(0);
+
+        // The wide cell doesn't fit after "ab" on a 3 column line
+        assert_eq!(
+            row.layout(3),
+            vec![(0, 0), (0, 1), (1, 0), (1, 2), (2, 0), (2, 1)]
+        );
+
+        // Each display line is padded to fill the width
+        let lines: Vec<String> = row
+            .split(3, &[])
+            .iter()
+            .map(|line| line.iter().map(|cell| cell.grapheme.as_str()).collect())
+            .collect();
+        assert_eq!(lines, vec!["ab ", "ぁc", "d  "]);
+
+        // A full line puts the end of the row on the next line
+        assert_eq!(row.layout(6).last(), Some(&(1, 0)));
+        assert_eq!(row.split(6, &[]).len(), 2);
+    }
+
+    #[test]
+    fn keeps_its_name_when_saving_as_fails() {
+        let fixture = Fixture::new("save-as", &[("b.txt", "")]);
+        let mut document = Document::blank();
+        document.insert("a");
+
+        assert!(document.save_as(fixture.path("missing/a.txt")).is_err());
+        assert!(document.filename.is_none() && document.path().is_none());
+        assert!(document.is_dirty());
+
+        let filename = fixture.path("a.txt");
+        document.save_as(filename.clone()).unwrap();
+        assert_eq!(document.filename, Some(filename.clone()));
+        assert_eq!(fs::read_to_string(&filename).unwrap(), "a\n");
+        assert!(!document.is_dirty());
+    }
+
+    #[test]
+    fn dirty_until_saved_state_restored() {
+        let mut document = Document::blank();
+        assert!(!document.is_dirty());
+
+        document.insert("a");
+        assert!(document.is_dirty());
+
+        document.undo();
+        assert!(!document.is_dirty());
+
+        document.redo();
+        assert!(document.is_dirty());
+
+        // Pretend the document was saved after typing "a"
+        document.history.mark_saved();
+        assert!(!document.is_dirty());
+
+        document.insert("b");
+        assert!(document.is_dirty());
+        document.undo();
+        assert!(!document.is_dirty());
+
+        // The saved state can't be reached again once it's dropped from the
+        // redo stack
+        document.undo();
+        document.insert("c");
+        document.undo();
+        assert!(document.is_dirty());
+    }
 }
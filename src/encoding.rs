@@ -0,0 +1,115 @@
+use std::io;
+use std::io::ErrorKind;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+impl Encoding {
+    // Accepts names like `UTF-8`, `utf16le` and `ISO-8859-1`.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        let name: String = name
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+
+        match name.as_str() {
+            "utf8" => Some(Encoding::Utf8),
+            "utf16le" => Some(Encoding::Utf16Le),
+            "utf16be" => Some(Encoding::Utf16Be),
+            "latin1" | "iso88591" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "Latin-1",
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> io::Result<String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|_| invalid(format!("the file isn't valid {}", self.name()))),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(invalid(format!("the file isn't valid {}", self.name())));
+                }
+
+                let units: Vec<u16> = bytes
+                    .chunks(2)
+                    .map(|pair| match self {
+                        Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+
+                // a byte order mark isn't part of the text
+                let units = units.strip_prefix(&[0xfeff]).unwrap_or(&units);
+
+                String::from_utf16(units)
+                    .map_err(|_| invalid(format!("the file isn't valid {}", self.name())))
+            }
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Encoding::Latin1 => text
+                .chars()
+                .map(|c| {
+                    u8::try_from(c)
+                        .map_err(|_| invalid(format!("'{}' can't be saved as {}", c, self.name())))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::Encoding;
+
+    #[test]
+    fn round_trips_text() {
+        let text = "façade ぁ\n";
+
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            let bytes = encoding.encode(text).unwrap();
+            assert_eq!(encoding.decode(&bytes).unwrap(), text);
+        }
+
+        let latin1 = Encoding::Latin1.encode("façade").unwrap();
+        assert_eq!(latin1, b"fa\xe7ade");
+        assert_eq!(Encoding::Latin1.decode(&latin1).unwrap(), "façade");
+        assert!(Encoding::Latin1.encode("ぁ").is_err());
+    }
+
+    #[test]
+    fn reads_names_and_byte_order_marks() {
+        assert_eq!(Encoding::from_name("UTF-8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("iso-8859-1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("ebcdic"), None);
+
+        let bytes = [0xff, 0xfe, b'h', 0, b'i', 0];
+        assert_eq!(Encoding::Utf16Le.decode(&bytes).unwrap(), "hi");
+        assert!(Encoding::Utf8.decode(b"\xff").is_err());
+    }
+}
@@ -0,0 +1,41 @@
+// Directories of files made for tests, so that what they find on disk
+// doesn't change with the files in the working tree.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+// A directory under the temporary directory holding the given files, which
+// is removed again when dropped.
+pub struct Fixture {
+    pub root: PathBuf,
+}
+
+impl Fixture {
+    // Tests run at the same time, so each must use a `name` of its own.
+    pub fn new(name: &str, files: &[(&str, &str)]) -> Fixture {
+        let root = env::temp_dir().join(format!("takkun-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        for (path, text) in files {
+            let path = root.join(path);
+
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        Fixture { root }
+    }
+
+    // Returns `path` under the root, as typed in a prompt.
+    pub fn path(&self, path: &str) -> String {
+        format!("{}/{}", self.root.display(), path)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,102 @@
+// Whether `c` starts a word, following one of the separators in a path or
+// the lower case end of a camel case word.
+fn is_boundary(prev: char, c: char) -> bool {
+    matches!(prev, '/' | '_' | '-' | '.' | ' ') || (prev.is_lowercase() && c.is_uppercase())
+}
+
+fn same(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+// Scores how well `candidate` matches `query`, whose characters have to
+// appear in it in order but not necessarily next to each other, or returns
+// None if it doesn't match. Runs of characters, matches at the start of
+// words and matches in the file name score higher, and gaps lower.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let name = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+
+    let mut score = 0;
+    let mut last: Option<usize> = None;
+    let mut i = 0;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        while i < chars.len() && !same(chars[i], q) {
+            i += 1;
+        }
+
+        if i == chars.len() {
+            return None;
+        }
+
+        score += 1;
+
+        match last {
+            Some(l) if l + 1 == i => score += 8,
+            Some(l) => score -= (i - l - 1).min(10) as i64,
+            None => {}
+        }
+
+        if i == 0 || is_boundary(chars[i - 1], chars[i]) {
+            score += 6;
+        }
+
+        if i >= name {
+            score += 2;
+        }
+
+        last = Some(i);
+        i += 1;
+    }
+
+    Some(score)
+}
+
+// Returns up to `limit` of the candidates matching `query`, best first.
+// Equally good matches are ordered by length and then alphabetically.
+pub fn rank(query: &str, candidates: &[String], limit: usize) -> Vec<String> {
+    let mut scored: Vec<(i64, &String)> = candidates
+        .iter()
+        .filter_map(|c| Some((score(query, c)?, c)))
+        .collect();
+
+    scored.sort_by(|(a, x), (b, y)| b.cmp(a).then(x.len().cmp(&y.len())).then(x.cmp(y)));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, c)| c.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuzzy::rank;
+    use crate::fuzzy::score;
+
+    #[test]
+    fn matches_in_order() {
+        assert!(score("mn", "src/main.rs").is_some());
+        assert!(score("MAIN", "src/main.rs").is_some());
+        assert!(score("nm", "src/main.rs").is_none());
+        assert!(score("", "anything").is_some());
+    }
+
+    #[test]
+    fn ranks_better_matches_first() {
+        let files: Vec<String> = [
+            "src/ui/text_area.rs",
+            "src/ui/tabs.rs",
+            "README.md",
+            "src/terminal.rs",
+        ]
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+
+        assert_eq!(rank("tabs", &files, 10)[0], "src/ui/tabs.rs");
+        assert_eq!(rank("ta", &files, 10)[0], "src/ui/tabs.rs");
+        assert_eq!(rank("txa", &files, 10), vec!["src/ui/text_area.rs"]);
+        assert_eq!(rank("term", &files, 1), vec!["src/terminal.rs"]);
+    }
+}
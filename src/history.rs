@@ -0,0 +1,125 @@
+use crate::document::Cursor;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Kind {
+    Insert,
+    Backspace,
+    Delete,
+    Other,
+}
+
+// A single replacement of `removed` with `inserted` starting at byte `at` of
+// the text. Lines in either string are separated by '\n'. The offset is kept
+// rather than a position, as the edit may join or split the graphemes around
+// it and leave no position that marks where it starts or ends.
+#[derive(Clone)]
+pub struct Edit {
+    pub at: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+// A group of edits that is undone and redone as one step.
+#[derive(Clone)]
+pub struct Change {
+    pub edits: Vec<Edit>,
+    pub before: Cursor,
+    pub after: Cursor,
+    kind: Kind,
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    open: bool,
+    // The length of the undo stack when the document was last saved, or
+    // None if that state was dropped from the redo stack.
+    saved: Option<usize>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            saved: Some(0),
+            ..History::default()
+        }
+    }
+
+    // Drops the undone changes, which can't be redone once there is a new
+    // change.
+    fn clear_redo(&mut self) {
+        self.redo.clear();
+
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+    }
+
+    pub fn record(&mut self, kind: Kind, edit: Edit, before: Cursor, after: Cursor) {
+        self.clear_redo();
+
+        // Consecutive edits of the same kind that pick up where the last one
+        // left the cursor are merged, so a run of typing is undone at once.
+        if self.open
+            && kind != Kind::Other
+            && let Some(last) = self.undo.last_mut()
+            && last.kind == kind
+            && last.after == before
+        {
+            last.edits.push(edit);
+            last.after = after;
+            return;
+        }
+
+        self.undo.push(Change {
+            edits: vec![edit],
+            before,
+            after,
+            kind,
+        });
+        self.open = kind != Kind::Other;
+    }
+
+    // Records edits made together, such as replacing every match of a
+    // search, as a single change. The edits are undone in reverse order.
+    pub fn record_all(&mut self, edits: Vec<Edit>, before: Cursor, after: Cursor) {
+        self.clear_redo();
+
+        self.undo.push(Change {
+            edits,
+            before,
+            after,
+            kind: Kind::Other,
+        });
+        self.open = false;
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+        self.open = false;
+    }
+
+    pub fn modified(&self) -> bool {
+        self.saved != Some(self.undo.len())
+    }
+
+    // Stops the next edit from being merged into the previous change.
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    pub fn undo(&mut self) -> Option<Change> {
+        self.open = false;
+        let change = self.undo.pop()?;
+        self.redo.push(change.clone());
+        Some(change)
+    }
+
+    pub fn redo(&mut self) -> Option<Change> {
+        self.open = false;
+        let change = self.redo.pop()?;
+        self.undo.push(change.clone());
+        Some(change)
+    }
+}
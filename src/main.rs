@@ -1,18 +1,44 @@
+#[cfg(test)]
+mod bench;
+mod cli;
+mod clipboard;
+mod config;
 mod document;
+mod encoding;
+#[cfg(test)]
+mod fixture;
+mod fuzzy;
+mod history;
+mod path;
+mod pattern;
+mod screen;
+mod search;
 mod style;
 #[macro_use]
 mod terminal;
+mod text;
 mod ui;
+mod walk;
 
 use std::env;
 use std::io;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process;
 
+use cli::Command;
+use cli::Target;
+use config::Config;
 use document::Cursor;
 use document::Document;
 use terminal::Event;
+use ui::Clipboard;
 use ui::Component;
+use ui::Confirm;
 use ui::FileChooser;
+use ui::FileFinder;
 use ui::Find;
+use ui::ProjectSearch;
 use ui::Status;
 use ui::Tabs;
 use ui::TextArea;
@@ -29,25 +55,7 @@ fn draw_rows(editor: &mut Editor, prev: &Window, write: &Box<terminal::Out>) ->
 
     let window = editor.root.render(editor.width, editor.height);
 
-    for i in 1..editor.height + 1 {
-        let line = window.lines.get(i - 1).unwrap();
-        let prev_line = prev.lines.get(i - 1);
-
-        if let Some(p) = prev_line {
-            if p == line {
-                write(position_cursor!(document::Cursor { x: 0, y: i }))?;
-                continue;
-            }
-        }
-
-        // TODO: reset line style
-        write(line.as_bytes())?;
-
-        if i < editor.height {
-            write(b"\r\n")?;
-        }
-    }
-
+    write(screen::diff(&prev.lines, &window.lines, config::get().color).as_bytes())?;
     write(position_cursor!(window.cursor))?;
 
     Ok(window)
@@ -59,7 +67,6 @@ fn refresh_screen(
     write: &Box<terminal::Out>,
 ) -> io::Result<Window> {
     write(terminal::HIDE_CURSOR)?;
-    write(terminal::ZERO_CURSOR)?;
 
     let window = draw_rows(editor, prev, write)?;
 
@@ -71,22 +78,23 @@ fn refresh_screen(
 struct Editor {
     width: usize,
     height: usize,
-    root: Box<dyn Component>,
+    root: Box<Confirm>,
 }
 
 impl Editor {
-    fn new() -> Editor {
+    fn new(document: Document) -> Editor {
         Editor {
             width: 0,
             height: 0,
-            root: Editor::create_root(Document::blank()),
+            root: Editor::create_root(document),
         }
     }
 
-    fn create_root(document: Document) -> Box<dyn Component> {
-        Status::new(FileChooser::new(Find::new(Tabs::new(TextArea::new(
-            document,
-        )))))
+    fn create_root(document: Document) -> Box<Confirm> {
+        let tabs = Tabs::new(TextArea::new(document));
+        let files = FileFinder::new(FileChooser::new(Find::new(Clipboard::new(tabs))));
+
+        Confirm::new(Status::new(ProjectSearch::new(files, PathBuf::from("."))))
     }
 
     fn update(&mut self, event: &Event) -> io::Result<bool> {
@@ -105,20 +113,69 @@ impl Editor {
         refresh_screen(self, &prev, write)
     }
 
+    // Opens each target in a tab of its own, in order, and selects the first
+    // tab. The first file goes in the tab the editor starts with unless that
+    // already holds a document. Files that can't be opened are left out and
+    // the last error is shown.
+    fn open(&mut self, targets: Vec<Target>, first_tab_free: bool) -> io::Result<()> {
+        let mut opened: Vec<PathBuf> = vec![];
+        let mut tabs = if first_tab_free { 0 } else { 1 };
+        let mut error = None;
+
+        for target in targets {
+            let canonical = path::canonical(&target.filename);
+
+            if opened.contains(&canonical) {
+                continue;
+            }
+
+            if tabs > 0 {
+                self.update(&Event::New)?;
+            }
+
+            if let Err(e) = self.root.document().open(target.filename) {
+                error = Some(e);
+
+                if tabs > 0 {
+                    self.update(&Event::Close)?;
+                }
+
+                continue;
+            }
+
+            if target.line.is_some() || target.column.is_some() {
+                self.root
+                    .document()
+                    .go_to(target.line.unwrap_or(1), target.column.unwrap_or(1));
+            }
+
+            opened.push(canonical);
+            tabs += 1;
+        }
+
+        // the tab of the last file is the last one, so the next is the first
+        if tabs > 1 {
+            self.update(&Event::Next)?;
+        }
+
+        if let Some(e) = error {
+            self.update(&Event::Error(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
     fn run(
         mut self,
-        filename: Option<String>,
+        targets: Vec<Target>,
+        first_tab_free: bool,
         read: Box<terminal::In>,
         write: Box<terminal::Out>,
     ) -> io::Result<()> {
         let mut paused = false;
         let mut dirty = true;
 
-        if let Some(f) = filename {
-            if let Err(e) = self.root.document().open(f) {
-                self.update(&Event::Error(e.to_string()))?;
-            }
-        }
+        self.open(targets, first_tab_free)?;
 
         let mut prev = Window {
             lines: vec![],
@@ -143,13 +200,13 @@ impl Editor {
                         prev.lines = vec![];
                         terminal::resume()?;
                     }
-                    Event::Exit => {
-                        // TODO: propagate this event to check for unsaved files
-                        break 'outer;
-                    }
                     e => dirty = self.update(e)? || dirty,
                 }
             }
+
+            if self.root.quit() {
+                break 'outer;
+            }
         }
 
         Ok(())
@@ -157,11 +214,58 @@ impl Editor {
 }
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let filename = args.get(1).cloned();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // errors are printed before the terminal switches to the alternate
+    // buffer, where they wouldn't be seen
+    let options = match cli::parse(&args) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            println!("takkun {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("takkun: {}", e);
+            eprintln!("Try 'takkun --help' for more information.");
+            process::exit(2);
+        }
+    };
+
+    let mut config = match Config::load(options.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("takkun: {}", e);
+            process::exit(1);
+        }
+    };
+
+    config.readonly = options.readonly;
+    config.encoding = options.encoding.unwrap_or(config.encoding);
+    config.color = config.color && !options.no_color;
+    config::set(config);
+
+    // text piped in, or asked for with `-`, is opened like `less` or `vim -`
+    // would, in the first tab
+    let piped = options.stdin || !io::stdin().is_terminal();
+    let document = if piped {
+        match Document::from_reader(io::stdin()) {
+            Ok(document) => document,
+            Err(e) => {
+                eprintln!("takkun: can't read stdin: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        Document::blank()
+    };
+
     let (read_input, write_output) = terminal::init()?;
 
-    Editor::new().run(filename, read_input, write_output)?;
+    Editor::new(document).run(options.targets, !piped, read_input, write_output)?;
 
     terminal::exit()?;
 
@@ -0,0 +1,216 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Replaces a leading `~` with the home directory.
+pub fn expand_home(path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return path.to_string(),
+    };
+
+    match env::var("HOME") {
+        Ok(home) => format!("{}{}", home.trim_end_matches('/'), rest),
+        Err(_) => path.to_string(),
+    }
+}
+
+// Resolves `path` to an absolute path without symlinks, `.` or `..`, so a
+// file has the same path however it was named. Files that don't exist yet
+// are resolved through their directory.
+pub fn canonical(path: &str) -> PathBuf {
+    let path = Path::new(path);
+
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+
+            match fs::canonicalize(dir) {
+                Ok(dir) => dir.join(name),
+                Err(_) => path.to_path_buf(),
+            }
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+// Splits a path as typed into the directory part, including its trailing
+// `/`, and the partial name after it.
+fn split_partial(input: &str) -> (&str, &str) {
+    match input.rfind('/') {
+        Some(i) => input.split_at(i + 1),
+        None if input == "~" => (input, ""),
+        None => ("", input),
+    }
+}
+
+// Returns the names of the entries in the directory part of `input` that
+// start with its last component, sorted, with directories ending in `/`.
+// Hidden entries are only listed when the partial name starts with a dot.
+pub fn completions(input: &str) -> Vec<String> {
+    let (dir, partial) = split_partial(input);
+    let dir = if dir.is_empty() {
+        ".".to_string()
+    } else {
+        expand_home(dir)
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+
+            if !name.starts_with(partial) || (name.starts_with('.') && !partial.starts_with('.')) {
+                return None;
+            }
+
+            // follows symlinks so links to directories complete like them
+            if entry.path().is_dir() {
+                Some(format!("{}/", name))
+            } else {
+                Some(name)
+            }
+        })
+        .collect();
+
+    names.sort();
+    names
+}
+
+// Completes `input` as far as all of `names` agree, returning None if that
+// doesn't add anything.
+pub fn complete(input: &str, names: &[String]) -> Option<String> {
+    let mut common = names.first()?.clone();
+
+    for name in &names[1..] {
+        while !name.starts_with(common.as_str()) {
+            common.pop();
+        }
+    }
+
+    let dir = match split_partial(input).0 {
+        "~" => "~/",
+        dir => dir,
+    };
+    let completed = format!("{}{}", dir, common);
+
+    if completed == input {
+        None
+    } else {
+        Some(completed)
+    }
+}
+
+// Gives errors about `path` a message that names it.
+pub fn describe(path: &str, error: io::Error) -> io::Error {
+    let message = match error.kind() {
+        ErrorKind::NotFound => format!("{} does not exist", path),
+        ErrorKind::PermissionDenied => format!("Permission denied: {}", path),
+        ErrorKind::IsADirectory => format!("{} is a directory", path),
+        _ => format!("{}: {}", path, error),
+    };
+
+    io::Error::new(error.kind(), message)
+}
+
+// Checks that `path` can be opened as a document, which it can if it is a
+// readable file or doesn't exist yet.
+pub fn check_readable(path: &str) -> io::Result<()> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+
+    if Path::new(path).is_dir() {
+        return Err(describe(path, io::Error::from(ErrorKind::IsADirectory)));
+    }
+
+    File::open(path).map_err(|e| describe(path, e))?;
+    Ok(())
+}
+
+// Checks that a document can be saved to `path`.
+pub fn check_writable(path: &str) -> io::Result<()> {
+    if Path::new(path).is_dir() {
+        return Err(describe(path, io::Error::from(ErrorKind::IsADirectory)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixture::Fixture;
+    use crate::path::canonical;
+    use crate::path::complete;
+    use crate::path::completions;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn completes_common_prefix() {
+        assert_eq!(
+            complete("src/d", &names(&["document.rs"])),
+            Some("src/document.rs".to_string())
+        );
+        assert_eq!(
+            complete("s", &names(&["style.rs", "status.rs"])),
+            Some("st".to_string())
+        );
+        assert_eq!(complete("st", &names(&["style.rs", "status.rs"])), None);
+        assert_eq!(complete("~", &names(&["a/"])), Some("~/a/".to_string()));
+        assert_eq!(complete("x", &[]), None);
+    }
+
+    #[test]
+    fn resolves_the_same_file_to_one_path() {
+        let main = canonical("src/main.rs");
+
+        assert!(main.is_absolute());
+        assert_eq!(canonical("./src/../src/main.rs"), main);
+        assert_eq!(canonical("src/new.rs"), main.with_file_name("new.rs"));
+    }
+
+    #[test]
+    fn lists_matching_entries() {
+        let fixture = Fixture::new(
+            "completions",
+            &[
+                (".git/HEAD", ""),
+                ("README.md", ""),
+                ("src/main.rs", ""),
+                ("src/ui/status.rs", ""),
+                ("src/ui/tabs.rs", ""),
+                ("src/ui/text_area.rs", ""),
+            ],
+        );
+
+        assert_eq!(
+            completions(&fixture.path("src/ui/t")),
+            names(&["tabs.rs", "text_area.rs"])
+        );
+        assert_eq!(
+            completions(&fixture.path("")),
+            names(&["README.md", "src/"])
+        );
+        assert_eq!(completions(&fixture.path(".gi")), names(&[".git/"]));
+    }
+}
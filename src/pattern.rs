@@ -0,0 +1,197 @@
+// Turns what is typed in the find prompts, with the options toggled there,
+// into a regular expression, and expands replacements for its matches.
+// `^` and `$` match at line starts and ends, so a pattern can be run over
+// many lines at once.
+
+use regex::Captures;
+use regex::Regex;
+use regex_syntax::hir::Class;
+use regex_syntax::hir::Hir;
+use regex_syntax::hir::HirKind;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Flags {
+    // match the pattern as plain text
+    pub literal: bool,
+    pub ignore_case: bool,
+    // only match where no word continues before or after the match
+    pub whole_word: bool,
+}
+
+pub struct Pattern {
+    pub regex: Regex,
+    // whether a match can run on past the end of a line
+    pub spans_lines: bool,
+}
+
+// Returns the last line of a parse error, which says what is wrong without
+// drawing the pattern.
+fn describe(error: &str) -> String {
+    let last = error.lines().last().unwrap_or_default();
+    last.trim_start_matches("error: ").to_string()
+}
+
+// Returns whether `hir` can match a newline.
+fn matches_newline(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Literal(literal) => literal.0.contains(&b'\n'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= '\n' && '\n' <= range.end()),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= b'\n' && b'\n' <= range.end()),
+        HirKind::Repetition(repetition) => matches_newline(&repetition.sub),
+        HirKind::Capture(capture) => matches_newline(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(matches_newline),
+        HirKind::Empty | HirKind::Look(_) => false,
+    }
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, flags: Flags) -> Result<Pattern, String> {
+        let modes = if flags.ignore_case { "(?mi)" } else { "(?m)" };
+        let body = match flags.literal {
+            true => regex::escape(pattern),
+            false => pattern.to_string(),
+        };
+
+        // the pattern is checked on its own so that it can't close the group
+        // it is put in for whole words
+        let hir = regex_syntax::parse(&format!("{}{}", modes, body))
+            .map_err(|e| describe(&e.to_string()))?;
+
+        let pattern = match flags.whole_word {
+            true => format!(r"{}\b{{start-half}}(?:{})\b{{end-half}}", modes, body),
+            false => format!("{}{}", modes, body),
+        };
+
+        let regex = Regex::new(&pattern).map_err(|e| describe(&e.to_string()))?;
+
+        Ok(Pattern {
+            regex,
+            spans_lines: matches_newline(&hir),
+        })
+    }
+}
+
+// Returns `replacement` with `$n` and `${n}` replaced by the text of group
+// n, `$$` by `$`, and `\n`, `\t` and `\\` by the chars they stand for.
+// Groups that didn't take part in the match are empty.
+pub fn expand(captures: &Captures, replacement: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = replacement.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('n')) => unescaped.push('\n'),
+            ('\\', Some('t')) => unescaped.push('\t'),
+            ('\\', Some('\\')) => unescaped.push('\\'),
+            (c, _) => {
+                unescaped.push(c);
+                continue;
+            }
+        }
+
+        chars.next();
+    }
+
+    let mut expanded = String::new();
+    captures.expand(&unescaped, &mut expanded);
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pattern::expand;
+    use crate::pattern::Flags;
+    use crate::pattern::Pattern;
+
+    fn find(pattern: &str, flags: Flags, text: &str) -> Vec<String> {
+        Pattern::new(pattern, flags)
+            .unwrap()
+            .regex
+            .find_iter(text)
+            .map(|m| m.as_str().to_string())
+            .collect()
+    }
+
+    fn regex(pattern: &str, text: &str) -> Vec<String> {
+        find(pattern, Flags::default(), text)
+    }
+
+    #[test]
+    fn matches_across_lines() {
+        assert_eq!(regex("^b", "ab\nbc"), vec!["b"]);
+        assert_eq!(regex("b$", "ab\nbc"), vec!["b"]);
+        assert_eq!(regex("a.c", "abc a\nc"), vec!["abc"]);
+        assert_eq!(regex("b\\s+b", "ab\n\n  bc"), vec!["b\n\n  b"]);
+    }
+
+    #[test]
+    fn knows_patterns_spanning_lines() {
+        for (pattern, spans) in [
+            ("a.c$", false),
+            ("[a-z]+", false),
+            ("a\\nb", true),
+            ("a\\s+b", true),
+            ("[^a]", true),
+        ] {
+            let pattern_spans = Pattern::new(pattern, Flags::default()).unwrap().spans_lines;
+            assert_eq!(pattern_spans, spans, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn expands_replacements() {
+        let pattern = Pattern::new("(\\w+) = (?:(x)|(\\w+))", Flags::default()).unwrap();
+        let captures = pattern.regex.captures("key = value").unwrap();
+
+        assert_eq!(expand(&captures, "$3: $1"), "value: key");
+        assert_eq!(expand(&captures, "${1}s$2 $$1\\n"), "keys $1\n");
+        assert_eq!(expand(&captures, "\\\\t\\t"), "\\t\t");
+    }
+
+    #[test]
+    fn applies_flags() {
+        let ignore_case = Flags {
+            ignore_case: true,
+            ..Flags::default()
+        };
+        let whole_word = Flags {
+            whole_word: true,
+            ..Flags::default()
+        };
+        let literal = Flags {
+            literal: true,
+            ..Flags::default()
+        };
+
+        assert_eq!(find("straße", ignore_case, "STRAßE"), vec!["STRAßE"]);
+        assert_eq!(find("[^a]", ignore_case, "A"), Vec::<String>::new());
+        assert_eq!(find("cat", whole_word, "cats cat scat"), vec!["cat"]);
+        assert_eq!(find("-x", whole_word, "a-x -x-"), vec!["-x"]);
+        assert_eq!(find("a.c", literal, "abc a.c"), vec!["a.c"]);
+        assert_eq!(find("(", literal, "f(x)"), vec!["("]);
+    }
+
+    #[test]
+    fn rejects_bad_patterns() {
+        assert_eq!(
+            Pattern::new("(a", Flags::default()).err(),
+            Some("unclosed group".to_string())
+        );
+
+        let whole_word = Flags {
+            whole_word: true,
+            ..Flags::default()
+        };
+
+        for pattern in ["a)", "[a", "*a", "a{3,2}", "\\q", "[z-a]", "a\\", "a)(?:b"] {
+            assert!(Pattern::new(pattern, whole_word).is_err(), "{}", pattern);
+        }
+    }
+}
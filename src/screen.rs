@@ -0,0 +1,156 @@
+// Draws frames of cells, writing only the cells that changed since the frame
+// before along with the cursor moves and styles needed to reach them.
+
+use crate::style::Decoration;
+use crate::style::Line;
+use crate::style::Style;
+
+const RESET: &str = "\x1b[0m";
+
+// Returns the escape sequences that change the style in use from `from` to
+// `to`, or from an unknown one when `from` is None. Colors are left out when
+// they are turned off, and a reversed style stands in for them.
+fn set_style(from: Option<&Style>, to: &Style, color: bool) -> String {
+    let mut codes = String::new();
+    let from = from.filter(|from| from.decoration == to.decoration);
+
+    if from.is_none() {
+        codes.push_str(RESET);
+
+        for d in &to.decoration {
+            match d {
+                Decoration::Italic => codes.push_str("\x1b[3m"),
+                Decoration::Underline => codes.push_str("\x1b[4m"),
+                Decoration::Reverse if !color => codes.push_str("\x1b[7m"),
+                Decoration::Reverse => {}
+            }
+        }
+    }
+
+    if !color {
+        return codes;
+    }
+
+    if from.is_none_or(|from| from.foreground != to.foreground) {
+        codes.push_str(&format!("\x1b[38;5;{}m", to.foreground));
+    }
+
+    if from.is_none_or(|from| from.background != to.background) {
+        codes.push_str(&format!("\x1b[48;5;{}m", to.background));
+    }
+
+    codes
+}
+
+// Returns what to write to turn the screen showing `prev` into `next`. A
+// frame of another size than the one before is drawn whole, as is the first.
+// The style is reset at the end so that nothing drawn after takes it on.
+pub fn diff(prev: &[Line], next: &[Line], color: bool) -> String {
+    let mut out = String::new();
+    // where the terminal's cursor is and the style it draws in
+    let mut at: Option<(usize, usize)> = None;
+    let mut style: Option<&Style> = None;
+
+    let same_size =
+        prev.len() == next.len() && prev.iter().zip(next).all(|(a, b)| a.len() == b.len());
+
+    for (y, line) in next.iter().enumerate() {
+        for (x, cell) in line.iter().enumerate() {
+            // the columns a wide grapheme covers are drawn with it
+            if cell.width == 0 {
+                continue;
+            }
+
+            let columns = x..(x + cell.width).min(line.len());
+
+            if same_size && prev[y][columns.clone()] == line[columns] {
+                continue;
+            }
+
+            match at {
+                Some(at) if at == (x, y) => {}
+                Some((from, row)) if row == y && from < x => {
+                    out.push_str(&format!("\x1b[{}C", x - from));
+                }
+                _ => out.push_str(&format!("\x1b[{};{}H", y + 1, x + 1)),
+            }
+
+            if style != Some(&cell.style) {
+                out.push_str(&set_style(style, &cell.style, color));
+                style = Some(&cell.style);
+            }
+
+            out.push_str(&cell.grapheme);
+            at = Some((x + cell.width, y));
+        }
+    }
+
+    if style.is_some() {
+        out.push_str(RESET);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::screen::diff;
+    use crate::style::line;
+    use crate::style::Decoration;
+    use crate::style::Line;
+    use crate::style::Style;
+
+    fn style(background: u8) -> Style {
+        Style {
+            foreground: 7,
+            background,
+            decoration: vec![],
+        }
+    }
+
+    fn frame(lines: &[&str]) -> Vec<Line> {
+        lines.iter().map(|text| line(&style(0), text, 4)).collect()
+    }
+
+    #[test]
+    fn draws_only_changes() {
+        let prev = frame(&["abcd", "efgh"]);
+
+        assert_eq!(
+            diff(&[], &prev, true),
+            "\x1b[1;1H\x1b[0m\x1b[38;5;7m\x1b[48;5;0mabcd\x1b[2;1Hefgh\x1b[0m"
+        );
+        assert_eq!(diff(&prev, &prev, true), "");
+
+        // runs on the same line are reached by moving forward
+        let mut next = frame(&["xbcy", "efgh"]);
+        assert_eq!(
+            diff(&prev, &next, true),
+            "\x1b[1;1H\x1b[0m\x1b[38;5;7m\x1b[48;5;0mx\x1b[2Cy\x1b[0m"
+        );
+
+        // only what changes of the style is set
+        next[1][1].style = style(4);
+        next[1][2].style = Style {
+            decoration: vec![Decoration::Underline],
+            ..style(4)
+        };
+        assert_eq!(
+            diff(&frame(&["xbcy", "efgh"]), &next, true),
+            "\x1b[2;2H\x1b[0m\x1b[38;5;7m\x1b[48;5;4mf\x1b[0m\x1b[4m\x1b[38;5;7m\x1b[48;5;4mg\x1b[0m"
+        );
+        assert_eq!(
+            diff(&frame(&["xbcy", "eggh"]), &next, false),
+            "\x1b[2;2H\x1b[0mf\x1b[0m\x1b[4mg\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn draws_wide_graphemes_whole() {
+        let prev = frame(&["abcd"]);
+        let next = frame(&["aぁd"]);
+
+        assert_eq!(diff(&prev, &next, false), "\x1b[1;2H\x1b[0mぁ\x1b[0m");
+        assert_eq!(diff(&next, &prev, false), "\x1b[1;2H\x1b[0mbc\x1b[0m");
+    }
+}
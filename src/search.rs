@@ -0,0 +1,123 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::fs;
+use std::path::Path;
+
+use crate::pattern::Pattern;
+use crate::walk;
+
+const MAX_FILES: usize = 50000;
+// Larger files are most likely data rather than source, and would make the
+// search slow.
+const MAX_SIZE: u64 = 4 * 1024 * 1024;
+// A NUL byte near the start marks a file as binary.
+const SNIFF_LEN: usize = 8000;
+
+// A line that matches, with lines and columns counted from 1 and columns
+// counted in graphemes as the cursor is.
+#[derive(Debug, PartialEq)]
+pub struct Hit {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+fn read_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_SIZE {
+        return None;
+    }
+
+    let bytes = fs::read(path).ok()?;
+
+    if bytes[..bytes.len().min(SNIFF_LEN)].contains(&0) {
+        return None;
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+// Returns the first match on each matching line of the text files under
+// `root` that aren't excluded by a .gitignore, stopping after `limit`.
+pub fn search(root: &Path, pattern: &Pattern, limit: usize) -> Vec<Hit> {
+    let mut hits = vec![];
+
+    for path in walk::walk(root, MAX_FILES) {
+        let text = match read_text(&root.join(&path)) {
+            Some(text) => text,
+            None => continue,
+        };
+
+        for (i, line) in text.lines().enumerate() {
+            let start = match pattern.regex.find(line) {
+                Some(m) => m.start(),
+                None => continue,
+            };
+
+            hits.push(Hit {
+                path: path.clone(),
+                line: i + 1,
+                column: line[..start].graphemes(false).count() + 1,
+                text: line.to_string(),
+            });
+
+            if hits.len() >= limit {
+                return hits;
+            }
+        }
+    }
+
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixture::Fixture;
+    use crate::pattern::Flags;
+    use crate::pattern::Pattern;
+    use crate::search::search;
+    use crate::search::Hit;
+
+    #[test]
+    fn finds_lines_in_files() {
+        let fixture = Fixture::new(
+            "search",
+            &[
+                (".gitignore", "/target\n"),
+                ("binary", "walk(\0"),
+                ("notes.txt", "walk( walk(\n\u{915}\u{93f} walk(\n"),
+                ("src/walk.rs", "pub fn walk(root) {}\n"),
+                ("target/walk.rs", "pub fn walk(root) {}\n"),
+            ],
+        );
+        let hit = |path: &str, line, column, text: &str| Hit {
+            path: path.to_string(),
+            line,
+            column,
+            text: text.to_string(),
+        };
+
+        let pattern = Pattern::new(r"^pub fn walk\(", Flags::default()).unwrap();
+        assert_eq!(
+            search(&fixture.root, &pattern, 100),
+            vec![hit("src/walk.rs", 1, 1, "pub fn walk(root) {}")]
+        );
+
+        // columns count graphemes as the cursor does, where a spacing mark
+        // is one of its own
+        let literal = Flags {
+            literal: true,
+            ..Flags::default()
+        };
+        let pattern = Pattern::new("walk(", literal).unwrap();
+        assert_eq!(
+            search(&fixture.root, &pattern, 100),
+            vec![
+                hit("notes.txt", 1, 1, "walk( walk("),
+                hit("notes.txt", 2, 4, "\u{915}\u{93f} walk("),
+                hit("src/walk.rs", 1, 8, "pub fn walk(root) {}"),
+            ]
+        );
+        assert_eq!(search(&fixture.root, &pattern, 2).len(), 2);
+    }
+}
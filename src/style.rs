@@ -1,7 +1,13 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 #[derive(Clone, PartialEq)]
 pub enum Decoration {
     Italic,
     Underline,
+    // Only drawn when colors are turned off, to stand in for them on
+    // selections and bars.
+    Reverse,
 }
 
 #[derive(Clone, PartialEq)]
@@ -11,29 +17,110 @@ pub struct Style {
     pub decoration: Vec<Decoration>,
 }
 
-fn decoration(style: &Style) -> String {
-    let mut decorations = String::new();
+impl Style {
+    pub fn inverted(&self) -> Style {
+        let mut decoration = self.decoration.clone();
+
+        match decoration.iter().position(|d| *d == Decoration::Reverse) {
+            Some(i) => {
+                decoration.remove(i);
+            }
+            None => decoration.push(Decoration::Reverse),
+        }
 
-    for d in &style.decoration {
-        match d {
-            Decoration::Italic => decorations.push_str("\x1b[3m"),
-            Decoration::Underline => decorations.push_str("\x1b[4m"),
+        Style {
+            foreground: self.background,
+            background: self.foreground,
+            decoration,
         }
     }
+}
+
+// Cuts `text` off or pads it with spaces to fill `width` columns.
+pub fn fit(text: &str, width: usize) -> String {
+    let mut line = String::new();
+    let mut used = 0;
+
+    for g in text.graphemes(true) {
+        if used + g.width() > width {
+            break;
+        }
+
+        line.push_str(g);
+        used += g.width();
+    }
+
+    line + &" ".repeat(width - used)
+}
+
+// One column of the screen. A grapheme wider than a column is followed by
+// cells without one for the other columns it covers.
+#[derive(Clone, PartialEq)]
+pub struct Cell {
+    pub grapheme: String,
+    pub width: usize,
+    pub style: Style,
+}
+
+pub type Line = Vec<Cell>;
 
-    decorations
+// Appends `text` to `line` in `style`, with tabs as four spaces. Graphemes
+// that take no columns are joined to the one before them.
+pub fn push(line: &mut Line, style: &Style, text: &str) {
+    for g in text.graphemes(true) {
+        if g == "\t" {
+            push(line, style, "    ");
+            continue;
+        }
+
+        let width = g.width();
+
+        if width == 0 {
+            if let Some(cell) = line.iter_mut().rev().find(|c| c.width > 0) {
+                cell.grapheme.push_str(g);
+            }
+            continue;
+        }
+
+        line.push(Cell {
+            grapheme: g.to_string(),
+            width,
+            style: style.clone(),
+        });
+
+        for _ in 1..width {
+            line.push(Cell {
+                grapheme: String::new(),
+                width: 0,
+                style: style.clone(),
+            });
+        }
+    }
 }
 
-pub fn tabbed(text: &String) -> String {
-    text.replace("\t", std::str::from_utf8(&[b' '; 4]).unwrap())
+// Cuts `line` off or pads it with spaces in `style` to fill `width`
+// columns. A wide grapheme that doesn't fit is left out whole.
+pub fn fill(line: &mut Line, style: &Style, width: usize) {
+    if line.len() > width {
+        let split = line[width].width == 0;
+        line.truncate(width);
+
+        if split {
+            while line.pop().is_some_and(|c| c.width == 0) {}
+        }
+    }
+
+    while line.len() < width {
+        push(line, style, " ");
+    }
 }
 
-pub fn styled(style: &Style, text: &String) -> String {
-    format!(
-        "\x1b[0m{}\x1b[38;5;{}m\x1b[48;5;{}m{}",
-        decoration(&style),
-        style.foreground,
-        style.background,
-        text,
-    )
+// Returns `text` in `style`, cut off or padded to fill `width` columns.
+pub fn line(style: &Style, text: &str, width: usize) -> Line {
+    let mut line = vec![];
+
+    push(&mut line, style, text);
+    fill(&mut line, style, width);
+
+    line
 }
@@ -1,18 +1,35 @@
+use std::fs::File;
 use std::io;
 use std::io::ErrorKind;
+use std::io::IsTerminal;
 use std::io::Read;
 use std::io::Write;
 use std::os::unix::io::AsRawFd;
 use std::panic;
 use std::ptr::addr_of_mut;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc;
 use std::sync::OnceLock;
 use std::thread;
 
+use crate::config;
+
 pub const HIDE_CURSOR: &[u8; 6] = b"\x1b[?25l";
 pub const SHOW_CURSOR: &[u8; 6] = b"\x1b[?25h";
-pub const ZERO_CURSOR: &[u8; 3] = b"\x1b[H";
-pub const CLEAR_LINE: &[u8; 3] = b"\x1b[K";
+pub const ENABLE_BRACKETED_PASTE: &[u8; 8] = b"\x1b[?2004h";
+pub const DISABLE_BRACKETED_PASTE: &[u8; 8] = b"\x1b[?2004l";
+
+pub const ENABLE_MOUSE: &[u8; 24] = b"\x1b[?1000h\x1b[?1002h\x1b[?1006h";
+pub const DISABLE_MOUSE: &[u8; 24] = b"\x1b[?1006l\x1b[?1002l\x1b[?1000l";
+pub const PUSH_KEYBOARD_FLAGS: &[u8; 5] = b"\x1b[>1u";
+pub const POP_KEYBOARD_FLAGS: &[u8; 4] = b"\x1b[<u";
+
+const PASTE_END: &[u8; 6] = b"\x1b[201~";
+
+// Set when the terminal supports the kitty keyboard protocol, in which case
+// keys that are ambiguous in the legacy encoding are reported as `CSI ... u`.
+static KITTY_KEYBOARD: AtomicBool = AtomicBool::new(false);
 
 macro_rules! position_cursor {
     ($c:expr) => {
@@ -47,6 +64,18 @@ static mut TERMIOS: libc::termios = libc::termios {
 
 static CELL: OnceLock<libc::termios> = OnceLock::new();
 
+// The controlling terminal, opened for keys when stdin is a pipe.
+static TTY: OnceLock<File> = OnceLock::new();
+
+#[derive(Clone, Debug)]
+pub enum Mouse {
+    Press,
+    Drag,
+    Release,
+    ScrollUp,
+    ScrollDown,
+}
+
 #[derive(Debug)]
 pub enum Event {
     Input(String),
@@ -58,10 +87,26 @@ pub enum Event {
 
     PageUp,
     PageDown,
+    ScrollUp,
+    ScrollDown,
+    Center,
 
     Home,
     End,
 
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectHome,
+    SelectEnd,
+    SelectAll,
+
+    Cut,
+    Copy,
+    PasteClipboard,
+    Paste(String),
+
     Tab,
     Delete,
     Backspace,
@@ -72,6 +117,9 @@ pub enum Event {
     Prev,
     New,
     Open,
+    OpenFile(String),
+    FindFile,
+    SearchFiles,
     Close,
 
     Nothing,
@@ -81,145 +129,441 @@ pub enum Event {
     Exit,
 
     Find,
+    FindPrev,
+    Replace,
+    ToggleRegex,
+    ToggleCase,
+    ToggleWord,
     Save,
 
+    Undo,
+    Redo,
+
+    WordLeft,
+    WordRight,
+    SelectWordLeft,
+    SelectWordRight,
+    DeleteWordLeft,
+    DeleteWordRight,
+
+    // zero based column and line
+    Mouse(Mouse, usize, usize),
+
     Resize(usize, usize),
 
     Error(String),
 }
 
-fn ctrl(k: char) -> char {
-    (k as u8 & 0x1f) as char
-}
-
-fn read_char(stdin: &mut io::Stdin) -> io::Result<char> {
+fn read_char(input: &mut impl Read) -> io::Result<char> {
     let mut buffer: [u8; 1] = [0];
-    stdin.read_exact(&mut buffer)?;
+    input.read_exact(&mut buffer)?;
     Ok(buffer[0] as char)
 }
 
-fn parse_tilda(stdin: &mut io::Stdin, event: Event) -> Event {
-    match read_char(stdin) {
-        Ok('~') => event,
-        _ => Event::Escape,
+// Reads the continuation bytes of a UTF-8 sequence starting with `first` and
+// returns the decoded character, or None if the sequence is invalid.
+fn read_utf8(input: &mut impl Read, first: u8) -> Option<String> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return None,
+    };
+
+    let mut bytes = vec![first];
+
+    for _ in 1..len {
+        bytes.push(read_char(input).ok()? as u8);
     }
+
+    String::from_utf8(bytes).ok()
 }
 
-fn parse_bracket(stdin: &mut io::Stdin) -> Event {
-    match read_char(stdin) {
-        Ok(c) => match c {
-            '1' => parse_tilda(stdin, Event::Home),
-            '3' => parse_tilda(stdin, Event::Delete),
-            '4' => parse_tilda(stdin, Event::End),
-            '5' => parse_tilda(stdin, Event::PageUp),
-            '6' => parse_tilda(stdin, Event::PageDown),
-            '7' => parse_tilda(stdin, Event::Home),
-            '8' => parse_tilda(stdin, Event::End),
-            'A' => Event::Up,
-            'B' => Event::Down,
-            'C' => Event::Right,
-            'D' => Event::Left,
-            'H' => Event::Home,
-            'F' => Event::End,
-            _ => Event::Escape,
-        },
-        _ => Event::Escape,
-    }
+// Modifier flags as encoded in xterm style sequences, where the parameter is
+// one more than the sum of 1 for shift, 2 for alt and 4 for ctrl.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
 }
 
-fn parse_o(stdin: &mut io::Stdin) -> Event {
-    match read_char(stdin) {
-        Ok(c) => match c {
-            'H' => Event::Home,
-            'F' => Event::End,
-            _ => Event::Escape,
-        },
-        _ => Event::Escape,
+impl Modifiers {
+    fn from_param(param: u32) -> Modifiers {
+        let bits = param.saturating_sub(1);
+
+        Modifiers {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            ctrl: bits & 4 != 0,
+        }
     }
 }
 
-fn parse_escape(stdin: &mut io::Stdin) -> Event {
-    match read_char(stdin) {
-        Ok(c) => match c {
-            '[' => parse_bracket(stdin),
-            'O' => parse_o(stdin),
-            _ => Event::Escape,
-        },
-        _ => Event::Escape,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+
+    Up,
+    Down,
+    Left,
+    Right,
+
+    PageUp,
+    PageDown,
+
+    Home,
+    End,
+
+    Insert,
+    Tab,
+    Delete,
+    Backspace,
+    Escape,
+    Enter,
+
+    F(u8),
+}
+
+// Maps a key and its modifiers to the event it is bound to, or
+// `Event::Nothing` if the combination isn't bound.
+fn key_event(key: Key, modifiers: Modifiers) -> Event {
+    let Modifiers { shift, alt, ctrl } = modifiers;
+
+    match (key, shift, alt, ctrl) {
+        (Key::Char(c), false, false, false) => Event::Input(c.to_string()),
+
+        (Key::Char('s'), false, false, true) => Event::Save,
+        (Key::Char('f'), false, false, true) => Event::Find,
+        (Key::Char('r'), false, false, true) => Event::Replace,
+        (Key::Char('q'), false, false, true) => Event::Exit,
+        (Key::Char('a'), false, false, true) => Event::SelectAll,
+        (Key::Char('x'), false, false, true) => Event::Cut,
+        (Key::Char('c'), false, false, true) => Event::Copy,
+        (Key::Char('v'), false, false, true) => Event::PasteClipboard,
+        (Key::Char('b'), false, false, true) => Event::Pause,
+        (Key::Char('z'), false, false, true) => Event::Undo,
+        (Key::Char('y'), false, false, true) => Event::Redo,
+        (Key::Char('z'), true, false, true) => Event::Redo,
+        (Key::Char('n'), false, false, true) => Event::Next,
+        (Key::Char('p'), false, false, true) => Event::Prev,
+        (Key::Char('t'), false, false, true) => Event::New,
+        (Key::Char('o'), false, false, true) => Event::Open,
+        (Key::Char('e'), false, false, true) => Event::FindFile,
+        (Key::Char('g'), false, false, true) => Event::SearchFiles,
+        (Key::Char('w'), false, false, true) => Event::Close,
+        (Key::Char('l'), false, false, true) => Event::Center,
+
+        (Key::Up, false, false, false) => Event::Up,
+        (Key::Down, false, false, false) => Event::Down,
+        (Key::Left, false, false, false) => Event::Left,
+        (Key::Right, false, false, false) => Event::Right,
+        (Key::PageUp, false, false, false) => Event::PageUp,
+        (Key::PageDown, false, false, false) => Event::PageDown,
+        (Key::Home, false, false, false) => Event::Home,
+        (Key::End, false, false, false) => Event::End,
+
+        (Key::Tab, false, false, false) => Event::Tab,
+        (Key::Delete, false, false, false) => Event::Delete,
+        (Key::Backspace, false, false, false) => Event::Backspace,
+        (Key::Escape, false, false, false) => Event::Escape,
+        (Key::Enter, false, false, false) => Event::Enter,
+
+        (Key::Up, true, false, false) => Event::SelectUp,
+        (Key::Down, true, false, false) => Event::SelectDown,
+        (Key::Left, true, false, false) => Event::SelectLeft,
+        (Key::Right, true, false, false) => Event::SelectRight,
+        (Key::Home, true, false, false) => Event::SelectHome,
+        (Key::End, true, false, false) => Event::SelectEnd,
+
+        (Key::Up, false, false, true) => Event::ScrollUp,
+        (Key::Down, false, false, true) => Event::ScrollDown,
+
+        (Key::Left, false, true, false) | (Key::Left, false, false, true) => Event::WordLeft,
+        (Key::Right, false, true, false) | (Key::Right, false, false, true) => Event::WordRight,
+        (Key::Left, true, true, false) | (Key::Left, true, false, true) => Event::SelectWordLeft,
+        (Key::Right, true, true, false) | (Key::Right, true, false, true) => Event::SelectWordRight,
+
+        (Key::Backspace, false, true, false) | (Key::Backspace, false, false, true) => {
+            Event::DeleteWordLeft
+        }
+        (Key::Delete, false, true, false) | (Key::Delete, false, false, true) => {
+            Event::DeleteWordRight
+        }
+
+        (Key::F(3), false, false, false) => Event::Find,
+        (Key::F(3), true, false, false) | (Key::Enter, true, false, false) => Event::FindPrev,
+        (Key::Char('r'), false, true, false) => Event::ToggleRegex,
+        (Key::Char('c'), false, true, false) => Event::ToggleCase,
+        (Key::Char('w'), false, true, false) => Event::ToggleWord,
+
+        _ => Event::Nothing,
     }
 }
 
-fn process_keypress() -> Event {
-    let mut stdin = io::stdin();
+// Reads pasted text up to the `ESC [ 201 ~` that ends a bracketed paste.
+fn read_paste(input: &mut impl Read) -> Event {
+    let mut bytes: Vec<u8> = vec![];
+    let mut timeouts = 0;
 
-    match read_char(&mut stdin) {
-        Ok(c) => {
-            if c == '\x1b' {
-                return parse_escape(&mut stdin);
+    while !bytes.ends_with(PASTE_END) {
+        match read_char(input) {
+            Ok(c) => {
+                bytes.push(c as u8);
+                timeouts = 0;
             }
-
-            if c == ctrl('s') {
-                return Event::Save;
+            // the terminal may pause in the middle of a large paste, but
+            // give up eventually rather than swallowing all input
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && timeouts < 10 => {
+                timeouts += 1;
             }
+            Err(_) => break,
+        }
+    }
 
-            if c == ctrl('f') {
-                return Event::Find;
-            }
+    if bytes.ends_with(PASTE_END) {
+        bytes.truncate(bytes.len() - PASTE_END.len());
+    }
 
-            if c == ctrl('q') {
-                return Event::Exit;
-            }
+    let text = String::from_utf8_lossy(&bytes)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n");
 
-            if c == ctrl('z') {
-                return Event::Pause;
-            }
+    Event::Paste(text)
+}
 
-            if c == ctrl('n') {
-                return Event::Next;
-            }
+// Returns the `index`th `;` separated parameter, ignoring any `:` separated
+// sub-parameters, or `default` if it is missing.
+fn param(params: &[&str], index: usize, default: u32) -> u32 {
+    params
+        .get(index)
+        .and_then(|p| p.split(':').next())
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(default)
+}
 
-            if c == ctrl('p') {
-                return Event::Prev;
-            }
+// Maps the key code of a kitty `CSI code ; modifiers u` report to a key.
+fn kitty_key(code: u32) -> Option<Key> {
+    match code {
+        9 => Some(Key::Tab),
+        13 => Some(Key::Enter),
+        27 => Some(Key::Escape),
+        127 => Some(Key::Backspace),
+        // codes from 57344 on are private use keys such as the keypad and
+        // media keys, which we don't bind
+        0..57344 => char::from_u32(code)
+            .filter(|c| !c.is_control())
+            .map(Key::Char),
+        _ => None,
+    }
+}
 
-            if c == ctrl('t') {
-                return Event::New;
-            }
+// Reads the parameters and final byte of a control sequence.
+fn read_csi(input: &mut impl Read) -> Option<(String, char)> {
+    let mut params = String::new();
 
-            if c == ctrl('o') {
-                return Event::Open;
-            }
+    loop {
+        match read_char(input) {
+            Ok(c) if ('\x30'..='\x3f').contains(&c) => params.push(c),
+            Ok(c) if ('\x40'..='\x7e').contains(&c) => return Some((params, c)),
+            _ => return None,
+        }
 
-            if c == ctrl('w') {
-                return Event::Close;
-            }
+        if params.len() > 32 {
+            return None;
+        }
+    }
+}
 
-            if c == 13 as char {
-                return Event::Enter;
-            }
+// Parses an SGR mouse report of the form `ESC [ < button ; x ; y M`, where
+// the final byte is `m` instead when a button is released.
+fn parse_mouse(params: &str, last: char) -> Event {
+    let params: Vec<&str> = params.split(';').collect();
+    let button = param(&params, 0, 0);
+    let x = param(&params, 1, 1).saturating_sub(1) as usize;
+    let y = param(&params, 2, 1).saturating_sub(1) as usize;
+
+    // the low bits pick the button, 32 is set for motion and 64 for the wheel
+    let mouse = match (button & 0x63, last) {
+        (64, _) => Mouse::ScrollUp,
+        (65, _) => Mouse::ScrollDown,
+        (0, 'm') => Mouse::Release,
+        (0, _) => Mouse::Press,
+        (32, _) => Mouse::Drag,
+        _ => return Event::Nothing,
+    };
 
-            if c == 8 as char || c == 127 as char {
-                return Event::Backspace;
-            }
+    Event::Mouse(mouse, x, y)
+}
 
-            if (c as u8) == 9 {
-                return Event::Tab;
-            }
+// Parses a control sequence of the form `ESC [ params final`.
+fn parse_csi(input: &mut impl Read) -> Event {
+    let (params, last) = match read_csi(input) {
+        Some(csi) => csi,
+        None => return Event::Escape,
+    };
 
-            if (c as u8) > 31 && (c as u8) < 127 {
-                return Event::Input(c.to_string());
-            }
+    if let Some(params) = params.strip_prefix('<')
+        && (last == 'M' || last == 'm')
+    {
+        return parse_mouse(params, last);
+    }
 
+    // private sequences such as late replies to our queries
+    if params.starts_with(['<', '=', '>', '?']) {
+        return Event::Nothing;
+    }
+
+    let params: Vec<&str> = params.split(';').collect();
+    let modifiers = Modifiers::from_param(param(&params, 1, 1));
+
+    if last == 'u' {
+        // super, hyper and meta aren't bound to anything
+        if param(&params, 1, 1).saturating_sub(1) & 0x38 != 0 {
             return Event::Nothing;
         }
 
+        return match kitty_key(param(&params, 0, 0)) {
+            Some(key) => key_event(key, modifiers),
+            None => Event::Nothing,
+        };
+    }
+
+    let key = match last {
+        'A' => Key::Up,
+        'B' => Key::Down,
+        'C' => Key::Right,
+        'D' => Key::Left,
+        'H' => Key::Home,
+        'F' => Key::End,
+        'P' => Key::F(1),
+        'Q' => Key::F(2),
+        'R' => Key::F(3),
+        'S' => Key::F(4),
+        'Z' => {
+            return key_event(
+                Key::Tab,
+                Modifiers {
+                    shift: true,
+                    ..modifiers
+                },
+            );
+        }
+        '~' => match param(&params, 0, 0) {
+            1 | 7 => Key::Home,
+            2 => Key::Insert,
+            3 => Key::Delete,
+            4 | 8 => Key::End,
+            5 => Key::PageUp,
+            6 => Key::PageDown,
+            n @ 11..=15 => Key::F(n as u8 - 10),
+            n @ 17..=21 => Key::F(n as u8 - 11),
+            n @ 23..=24 => Key::F(n as u8 - 12),
+            200 => return read_paste(input),
+            _ => return Event::Nothing,
+        },
+        _ => return Event::Nothing,
+    };
+
+    key_event(key, modifiers)
+}
+
+// Parses a sequence of the form `ESC O final`, which some terminals send
+// for arrows, Home, End and F1 to F4.
+fn parse_ss3(input: &mut impl Read) -> Event {
+    let key = match read_char(input) {
+        Ok('A') => Key::Up,
+        Ok('B') => Key::Down,
+        Ok('C') => Key::Right,
+        Ok('D') => Key::Left,
+        Ok('H') => Key::Home,
+        Ok('F') => Key::End,
+        Ok('P') => Key::F(1),
+        Ok('Q') => Key::F(2),
+        Ok('R') => Key::F(3),
+        Ok('S') => Key::F(4),
+        Ok(_) => return Event::Nothing,
+        Err(_) => return Event::Escape,
+    };
+
+    key_event(key, Modifiers::default())
+}
+
+fn parse_escape(input: &mut impl Read) -> Event {
+    match read_char(input) {
+        Ok('[') => parse_csi(input),
+        Ok('O') => parse_ss3(input),
+        // alt sends ESC before the key it modifies
+        Ok(c) if (c as u8) > 31 && (c as u8) < 127 => key_event(
+            Key::Char(c),
+            Modifiers {
+                alt: true,
+                ..Modifiers::default()
+            },
+        ),
+        Ok('\x7f') => key_event(
+            Key::Backspace,
+            Modifiers {
+                alt: true,
+                ..Modifiers::default()
+            },
+        ),
+        Ok(_) => Event::Nothing,
+        Err(_) => key_event(Key::Escape, Modifiers::default()),
+    }
+}
+
+fn read_event(input: &mut impl Read) -> Event {
+    match read_char(input) {
+        Ok(c) => match c as u8 {
+            0x1b => parse_escape(input),
+            13 => key_event(Key::Enter, Modifiers::default()),
+            9 => key_event(Key::Tab, Modifiers::default()),
+            127 => key_event(Key::Backspace, Modifiers::default()),
+            // terminals whose backspace sends 127 send 8 for ctrl+backspace
+            8 => key_event(
+                Key::Backspace,
+                Modifiers {
+                    ctrl: true,
+                    ..Modifiers::default()
+                },
+            ),
+            // ctrl clears the top bits of a letter, so ctrl+a arrives as 1
+            1..=26 => key_event(
+                Key::Char((c as u8 + 0x60) as char),
+                Modifiers {
+                    ctrl: true,
+                    ..Modifiers::default()
+                },
+            ),
+            32..127 => key_event(Key::Char(c), Modifiers::default()),
+            128.. => match read_utf8(input, c as u8) {
+                Some(s) => Event::Input(s),
+                None => Event::Nothing,
+            },
+            _ => Event::Nothing,
+        },
+
         Err(e) => match e.kind() {
             ErrorKind::UnexpectedEof => Event::Nothing,
-            _ => return Event::Error(e.to_string()),
+            _ => Event::Error(e.to_string()),
         },
     }
 }
 
+// Passes `f` the terminal input, which is stdin unless that is a pipe the
+// document was read from.
+fn with_input<T>(f: impl FnOnce(&mut dyn Read) -> T) -> T {
+    match TTY.get() {
+        Some(tty) => f(&mut &*tty),
+        None => f(&mut io::stdin()),
+    }
+}
+
+fn process_keypress() -> Event {
+    with_input(|mut input| read_event(&mut input))
+}
+
 pub fn raw_mode_termios(termios: &libc::termios) -> libc::termios {
     let mut raw_termios = termios.clone();
 
@@ -292,6 +636,10 @@ fn write(buffer: &[u8]) -> io::Result<()> {
 pub type In = dyn Fn() -> Vec<Event>;
 pub type Out = dyn Fn(&[u8]) -> io::Result<()>;
 
+pub fn output() -> Box<Out> {
+    Box::new(write)
+}
+
 pub fn enter_alternate_buffer() -> io::Result<()> {
     let mut stdout = io::stdout();
     stdout.write_all(b"\x1b[?1049h\x1b[2J\x1b[H")?;
@@ -306,6 +654,32 @@ pub fn exit_alternate_buffer() -> io::Result<()> {
     Ok(())
 }
 
+// Asks the terminal which kitty keyboard protocol flags are enabled. The
+// query is followed by a request for the primary device attributes, which
+// every terminal answers, so a terminal without support for the protocol
+// only answers the second one.
+fn query_kitty_keyboard(mut input: impl Read) -> io::Result<bool> {
+    let mut supported = false;
+    let mut timeouts = 0;
+
+    write(b"\x1b[?u\x1b[c")?;
+
+    while timeouts < 5 {
+        match read_char(&mut input) {
+            Ok('\x1b') => match (read_char(&mut input), read_csi(&mut input)) {
+                (Ok('['), Some((params, 'u'))) if params.starts_with('?') => supported = true,
+                (Ok('['), Some((params, 'c'))) if params.starts_with('?') => break,
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => timeouts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(supported)
+}
+
 pub fn init() -> io::Result<(Box<In>, Box<Out>)> {
     let stdout = io::stdout();
 
@@ -316,9 +690,20 @@ pub fn init() -> io::Result<(Box<In>, Box<Out>)> {
         libc::pipe(&raw mut PIPES[0]);
     }
 
+    if !io::stdin().is_terminal() {
+        let tty = File::open("/dev/tty")?;
+        let _ = TTY.set(tty);
+    }
+
     enter_alternate_buffer()?;
     enter_raw_mode()?;
 
+    // `keyboard = legacy` skips the query for terminals that mishandle it
+    if config::get().kitty_keyboard && with_input(|input| query_kitty_keyboard(input))? {
+        KITTY_KEYBOARD.store(true, Ordering::SeqCst);
+        write(PUSH_KEYBOARD_FLAGS)?;
+    }
+
     let default_panic_hook = panic::take_hook();
 
     panic::set_hook(Box::new(move |info| {
@@ -399,7 +784,7 @@ pub fn init() -> io::Result<(Box<In>, Box<Out>)> {
         events
     };
 
-    Ok((Box::new(read), Box::new(write)))
+    Ok((Box::new(read), output()))
 }
 
 pub fn exit() -> io::Result<()> {
@@ -421,7 +806,12 @@ pub fn enter_raw_mode() -> io::Result<()> {
         );
     }
 
-    Ok(())
+    if KITTY_KEYBOARD.load(Ordering::SeqCst) {
+        write(PUSH_KEYBOARD_FLAGS)?;
+    }
+
+    write(ENABLE_MOUSE)?;
+    write(ENABLE_BRACKETED_PASTE)
 }
 
 pub fn exit_raw_mode() -> io::Result<()> {
@@ -432,7 +822,12 @@ pub fn exit_raw_mode() -> io::Result<()> {
         libc::tcsetattr(stdout.as_raw_fd(), libc::TCSAFLUSH, CELL.get().unwrap());
     }
 
-    Ok(())
+    if KITTY_KEYBOARD.load(Ordering::SeqCst) {
+        write(POP_KEYBOARD_FLAGS)?;
+    }
+
+    write(DISABLE_MOUSE)?;
+    write(DISABLE_BRACKETED_PASTE)
 }
 
 pub fn pause() -> io::Result<()> {
@@ -453,3 +848,98 @@ pub fn resume() -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal::read_event;
+    use crate::terminal::Event;
+    use crate::terminal::Mouse;
+
+    fn parse(bytes: &[u8]) -> Event {
+        read_event(&mut &bytes[..])
+    }
+
+    #[test]
+    fn parses_modified_keys() {
+        assert!(matches!(parse(b"\x1b[A"), Event::Up));
+        assert!(matches!(parse(b"\x1b[1;2A"), Event::SelectUp));
+        assert!(matches!(parse(b"\x1b[1;5C"), Event::WordRight));
+        assert!(matches!(parse(b"\x1b[1;6D"), Event::SelectWordLeft));
+        assert!(matches!(parse(b"\x1b[3~"), Event::Delete));
+        assert!(matches!(parse(b"\x1b[3;5~"), Event::DeleteWordRight));
+        assert!(matches!(parse(b"\x1bOH"), Event::Home));
+        assert!(matches!(parse(b"\x1b"), Event::Escape));
+
+        assert!(matches!(parse(b"\x1bOR"), Event::Find));
+        assert!(matches!(parse(b"\x1b[13~"), Event::Find));
+        assert!(matches!(parse(b"\x1b[1;2R"), Event::FindPrev));
+
+        // Unbound combinations are ignored rather than read as Escape
+        assert!(matches!(parse(b"\x1b[2;5~"), Event::Nothing));
+        assert!(matches!(parse(b"\x1b[1;3R"), Event::Nothing));
+        assert!(matches!(parse(b"\x1bx"), Event::Nothing));
+    }
+
+    #[test]
+    fn parses_kitty_keys() {
+        // ctrl+i and ctrl+m are distinct from Tab and Enter
+        assert!(matches!(parse(b"\x1b[105;5u"), Event::Nothing));
+        assert!(matches!(parse(b"\x1b[9u"), Event::Tab));
+        assert!(matches!(parse(b"\x1b[27u"), Event::Escape));
+        assert!(matches!(parse(b"\x1b[115;5u"), Event::Save));
+        assert!(matches!(parse(b"\x1b[122;6u"), Event::Redo));
+        assert!(matches!(parse(b"\x1b[13;2u"), Event::FindPrev));
+        assert!(matches!(parse(b"\x1b[97u"), Event::Input(s) if s == "a"));
+        assert!(matches!(parse(b"\x1b[1;5:1C"), Event::WordRight));
+
+        // super+s and unsolicited replies are ignored
+        assert!(matches!(parse(b"\x1b[115;9u"), Event::Nothing));
+        assert!(matches!(parse(b"\x1b[?1u"), Event::Nothing));
+    }
+
+    #[test]
+    fn parses_mouse_reports() {
+        assert!(matches!(
+            parse(b"\x1b[<0;1;1M"),
+            Event::Mouse(Mouse::Press, 0, 0)
+        ));
+        assert!(matches!(
+            parse(b"\x1b[<32;10;5M"),
+            Event::Mouse(Mouse::Drag, 9, 4)
+        ));
+        assert!(matches!(
+            parse(b"\x1b[<0;10;5m"),
+            Event::Mouse(Mouse::Release, 9, 4)
+        ));
+        assert!(matches!(
+            parse(b"\x1b[<64;3;2M"),
+            Event::Mouse(Mouse::ScrollUp, 2, 1)
+        ));
+        assert!(matches!(
+            parse(b"\x1b[<65;3;2M"),
+            Event::Mouse(Mouse::ScrollDown, 2, 1)
+        ));
+        assert!(matches!(parse(b"\x1b[<2;3;2M"), Event::Nothing));
+    }
+
+    #[test]
+    fn parses_control_keys() {
+        assert!(matches!(parse(b"\x13"), Event::Save));
+        assert!(matches!(parse(b"\x1a"), Event::Undo));
+        assert!(matches!(parse(b"\r"), Event::Enter));
+        assert!(matches!(parse(b"\t"), Event::Tab));
+        assert!(matches!(parse(b"\x7f"), Event::Backspace));
+        assert!(matches!(parse(b"\x1b\x7f"), Event::DeleteWordLeft));
+        assert!(matches!(parse(b"\x08"), Event::DeleteWordLeft));
+        assert!(matches!(parse(b"\x1br"), Event::ToggleRegex));
+        assert!(matches!(parse("é".as_bytes()), Event::Input(s) if s == "é"));
+    }
+
+    #[test]
+    fn parses_bracketed_paste() {
+        match parse(b"\x1b[200~a\tb\r\nc\x1b[201~") {
+            Event::Paste(text) => assert_eq!(text, "a\tb\nc"),
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,363 @@
+// Text kept as a piece table. The text as it was loaded stays whole in one
+// buffer, everything inserted since is appended to another, and the text is
+// the run of pieces of the two that make it up now. The positions of the
+// newlines in both buffers are indexed as they are added, and each piece
+// keeps where it falls in the text, so finding an offset or a line is a
+// binary search over the pieces however many edits split them.
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Original,
+    Added,
+}
+
+#[derive(Clone, Copy)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+    newlines: usize,
+    // the offset of the piece in the text and the newlines before it
+    offset: usize,
+    line: usize,
+}
+
+struct Buffer {
+    text: String,
+    // the byte offset of each '\n' in the text
+    newlines: Vec<usize>,
+}
+
+impl Buffer {
+    fn new(text: String) -> Buffer {
+        let newlines = text.match_indices('\n').map(|(i, _)| i).collect();
+
+        Buffer { text, newlines }
+    }
+
+    fn push(&mut self, text: &str) {
+        let start = self.text.len();
+
+        self.newlines
+            .extend(text.match_indices('\n').map(|(i, _)| start + i));
+        self.text.push_str(text);
+    }
+
+    fn count_newlines(&self, start: usize, end: usize) -> usize {
+        self.newlines.partition_point(|&i| i < end) - self.newlines.partition_point(|&i| i < start)
+    }
+
+    // Returns the offset of the `n`th newline from `start`, counting from 0.
+    fn nth_newline(&self, start: usize, n: usize) -> usize {
+        self.newlines[self.newlines.partition_point(|&i| i < start) + n]
+    }
+}
+
+pub struct Text {
+    original: Buffer,
+    added: Buffer,
+    pieces: Vec<Piece>,
+    len: usize,
+}
+
+impl Text {
+    pub fn new(text: String) -> Text {
+        let original = Buffer::new(text);
+        let len = original.text.len();
+        let mut pieces = vec![];
+
+        if len > 0 {
+            pieces.push(Piece {
+                source: Source::Original,
+                start: 0,
+                len,
+                newlines: original.newlines.len(),
+                offset: 0,
+                line: 0,
+            });
+        }
+
+        Text {
+            original,
+            added: Buffer::new(String::new()),
+            pieces,
+            len,
+        }
+    }
+
+    fn buffer(&self, source: Source) -> &Buffer {
+        match source {
+            Source::Original => &self.original,
+            Source::Added => &self.added,
+        }
+    }
+
+    fn piece(&self, source: Source, start: usize, len: usize) -> Piece {
+        Piece {
+            source,
+            start,
+            len,
+            newlines: self.buffer(source).count_newlines(start, start + len),
+            offset: 0,
+            line: 0,
+        }
+    }
+
+    // Works out where the pieces from `first` on fall in the text, after
+    // those before them changed.
+    fn reindex(&mut self, first: usize) {
+        let (mut offset, mut line) = match first.checked_sub(1) {
+            Some(i) => {
+                let prev = &self.pieces[i];
+                (prev.offset + prev.len, prev.line + prev.newlines)
+            }
+            None => (0, 0),
+        };
+
+        for piece in &mut self.pieces[first..] {
+            piece.offset = offset;
+            piece.line = line;
+            offset += piece.len;
+            line += piece.newlines;
+        }
+    }
+
+    fn str(&self, piece: &Piece) -> &str {
+        &self.buffer(piece.source).text[piece.start..piece.start + piece.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn newlines(&self) -> usize {
+        self.pieces.last().map_or(0, |p| p.line + p.newlines)
+    }
+
+    // Returns the offset just past the `n`th newline, counting from 1, or 0
+    // for the 0th. Past the last newline it is the end of the text.
+    pub fn after_newline(&self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+
+        let i = self.pieces.partition_point(|p| p.line + p.newlines < n);
+
+        match self.pieces.get(i) {
+            Some(piece) => {
+                let at = self
+                    .buffer(piece.source)
+                    .nth_newline(piece.start, n - piece.line - 1);
+                piece.offset + at - piece.start + 1
+            }
+            None => self.len,
+        }
+    }
+
+    // Returns the number of newlines before `offset`, which is the line it
+    // is in.
+    pub fn line_at(&self, offset: usize) -> usize {
+        let (i, start) = self.find(offset);
+
+        match self.pieces.get(i) {
+            Some(piece) => {
+                piece.line
+                    + self
+                        .buffer(piece.source)
+                        .count_newlines(piece.start, piece.start + offset - start)
+            }
+            None => self.newlines(),
+        }
+    }
+
+    // Returns the index of the piece holding `offset` and where that piece
+    // starts, or the number of pieces and the length at the end.
+    fn find(&self, offset: usize) -> (usize, usize) {
+        let i = self.pieces.partition_point(|p| p.offset + p.len <= offset);
+
+        let end = self.pieces.last().map_or(0, |p| p.offset + p.len);
+
+        (i, self.pieces.get(i).map_or(end, |p| p.offset))
+    }
+
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        let mut text = String::with_capacity(end - start);
+        let (first, mut offset) = self.find(start);
+
+        for piece in &self.pieces[first..] {
+            if offset >= end {
+                break;
+            }
+
+            let from = start.saturating_sub(offset);
+            let to = (end - offset).min(piece.len);
+            text.push_str(&self.str(piece)[from..to]);
+            offset += piece.len;
+        }
+
+        text
+    }
+
+    // Returns the pieces of the text in order.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.pieces.iter().map(|piece| self.str(piece))
+    }
+
+    pub fn insert(&mut self, at: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let start = self.added.text.len();
+        self.added.push(text);
+        self.len += text.len();
+
+        let (i, offset) = self.find(at);
+
+        // typing extends the piece it is typed at the end of
+        if at == offset && i > 0 {
+            let prev = &mut self.pieces[i - 1];
+
+            if prev.source == Source::Added && prev.start + prev.len == start {
+                prev.len += text.len();
+                prev.newlines += self.added.count_newlines(start, start + text.len());
+                self.reindex(i);
+                return;
+            }
+        }
+
+        let new = self.piece(Source::Added, start, text.len());
+
+        if at == offset {
+            self.pieces.insert(i, new);
+            self.reindex(i);
+            return;
+        }
+
+        let piece = self.pieces[i];
+        let split = at - offset;
+        let left = self.piece(piece.source, piece.start, split);
+        let right = self.piece(piece.source, piece.start + split, piece.len - split);
+
+        self.pieces.splice(i..i + 1, [left, new, right]);
+        self.reindex(i);
+    }
+
+    pub fn remove(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+
+        let (first, offset) = self.find(start);
+        let mut kept = vec![];
+        let mut last = first;
+        let mut piece_start = offset;
+
+        for piece in &self.pieces[first..] {
+            if piece_start >= end {
+                break;
+            }
+
+            let piece_end = piece_start + piece.len;
+
+            if piece_start < start {
+                kept.push(self.piece(piece.source, piece.start, start - piece_start));
+            }
+
+            if piece_end > end {
+                let cut = end - piece_start;
+                kept.push(self.piece(piece.source, piece.start + cut, piece.len - cut));
+            }
+
+            last += 1;
+            piece_start = piece_end;
+        }
+
+        self.pieces.splice(first..last, kept);
+        self.len -= end - start;
+        self.reindex(first);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text::Text;
+
+    fn contents(text: &Text) -> String {
+        text.chunks().collect()
+    }
+
+    #[test]
+    fn edits_pieces() {
+        let mut text = Text::new("one\ntwo\n".to_string());
+
+        text.insert(4, "ab");
+        text.insert(6, "c\n");
+        text.insert(0, "0");
+        assert_eq!(contents(&text), "0one\nabc\ntwo\n");
+        assert_eq!(text.slice(3, 10), "e\nabc\nt");
+
+        text.remove(2, 7);
+        assert_eq!(contents(&text), "0oc\ntwo\n");
+        assert_eq!(text.len(), 8);
+
+        text.remove(0, 8);
+        assert_eq!(contents(&text), "");
+        text.insert(0, "x");
+        assert_eq!(contents(&text), "x");
+    }
+
+    #[test]
+    fn finds_lines() {
+        let mut text = Text::new("a\nbc\n".to_string());
+        text.insert(3, "\nd");
+
+        assert_eq!(contents(&text), "a\nb\ndc\n");
+        assert_eq!(text.newlines(), 3);
+        assert_eq!(
+            (0..=4)
+                .map(|n| text.after_newline(n))
+                .collect::<Vec<usize>>(),
+            vec![0, 2, 4, 7, 7]
+        );
+    }
+
+    #[test]
+    fn indexes_pieces_after_many_edits() {
+        let mut text = Text::new("a\nbc\n".repeat(50));
+        let mut expected = contents(&text);
+
+        // edits all over the text, some of them taking out whole pieces
+        for i in 0..500 {
+            let at = i * 37 % expected.len();
+
+            if i % 3 == 0 {
+                let end = (at + i % 5).min(expected.len());
+                text.remove(at, end);
+                expected.replace_range(at..end, "");
+            } else {
+                let inserted = if i % 2 == 0 { "x\n" } else { "yz" };
+                text.insert(at, inserted);
+                expected.insert_str(at, inserted);
+            }
+        }
+
+        assert_eq!(contents(&text), expected);
+        assert_eq!(text.newlines(), expected.matches('\n').count());
+
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(expected.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        for (n, start) in starts.iter().enumerate() {
+            assert_eq!(text.after_newline(n), *start);
+        }
+
+        for offset in 0..=expected.len() {
+            assert_eq!(
+                text.line_at(offset),
+                expected[..offset].matches('\n').count()
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::io;
+
+use crate::clipboard::Backend;
+use crate::document::Document;
+use crate::terminal::Event;
+use crate::ui::Component;
+use crate::ui::Window;
+
+pub struct Clipboard {
+    child: Box<dyn Component>,
+    contents: Option<String>,
+    backend: Backend,
+}
+
+impl Clipboard {
+    pub fn new(child: Box<dyn Component>) -> Box<Clipboard> {
+        Box::new(Clipboard {
+            child,
+            contents: None,
+            backend: Backend::from_config(),
+        })
+    }
+}
+
+impl Component for Clipboard {
+    fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
+        match e {
+            Event::Copy => {
+                // kept first, so the text can still be pasted if the
+                // system clipboard can't be reached
+                if let Some(text) = self.document().selected_text() {
+                    self.contents = Some(text.clone());
+                    self.backend.copy(&text)?;
+                }
+
+                Ok(false)
+            }
+            Event::Cut => match self.document().cut() {
+                Some(text) => {
+                    self.contents = Some(text.clone());
+                    self.backend.copy(&text)?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            Event::PasteClipboard => match self.backend.paste()?.or(self.contents.clone()) {
+                Some(text) => {
+                    self.document().paste(&text);
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            _ => self.child.update(e, width),
+        }
+    }
+
+    fn render(&mut self, width: usize, height: usize) -> Window {
+        self.child.render(width, height)
+    }
+
+    fn document(&mut self) -> &mut Document {
+        self.child.document()
+    }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clipboard::Backend;
+    use crate::document::Document;
+    use crate::terminal::Event;
+    use crate::ui::clipboard::Clipboard;
+    use crate::ui::Component;
+    use crate::ui::TextArea;
+
+    #[test]
+    fn keeps_text_when_the_helper_fails() {
+        let mut clipboard = Clipboard {
+            child: TextArea::new(Document::blank()),
+            contents: None,
+            backend: Backend::Helper {
+                copy: vec!["takkun-missing-helper"],
+                paste: vec!["takkun-missing-helper"],
+            },
+        };
+
+        clipboard.document().paste("cut me");
+        clipboard.document().select_all();
+        assert!(clipboard.update(&Event::Cut, 80).is_err());
+        assert_eq!(clipboard.document().line(0), "");
+
+        clipboard.update(&Event::PasteClipboard, 80).unwrap();
+        assert_eq!(clipboard.document().line(0), "cut me");
+    }
+}
@@ -0,0 +1,165 @@
+use std::io;
+
+use crate::document::Document;
+use crate::style::line;
+use crate::style::Decoration;
+use crate::style::Style;
+use crate::terminal::Event;
+use crate::ui::Component;
+use crate::ui::Window;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Close,
+    Exit,
+}
+
+// Asks whether to save documents with unsaved changes before they are
+// closed or the editor exits.
+pub struct Confirm {
+    child: Box<dyn Component>,
+    pending: Option<Action>,
+    saving: bool,
+    quit: bool,
+}
+
+impl Confirm {
+    pub fn new(child: Box<dyn Component>) -> Box<Confirm> {
+        Box::new(Confirm {
+            child,
+            pending: None,
+            saving: false,
+            quit: false,
+        })
+    }
+
+    // Returns true once every modified document has been saved or discarded
+    // after an exit was requested.
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    fn cancel(&mut self) {
+        self.pending = None;
+        self.saving = false;
+    }
+
+    // Finishes the pending action once the current document has been saved
+    // or discarded, moving on to the next modified document when exiting.
+    fn advance(&mut self, width: usize) -> io::Result<bool> {
+        self.saving = false;
+
+        match self.pending {
+            Some(Action::Close) => {
+                self.pending = None;
+                self.child.update(&Event::Close, width)?;
+            }
+            Some(Action::Exit) if self.child.select_modified() => {}
+            Some(Action::Exit) => {
+                self.pending = None;
+                self.quit = true;
+            }
+            None => {}
+        }
+
+        Ok(true)
+    }
+}
+
+impl Component for Confirm {
+    fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
+        // saving may need a filename first, so events go to the save prompt
+        // until the document is saved or the prompt is cancelled
+        if self.saving {
+            let dirty = self.child.update(e, width)?;
+
+            if !self.document().is_dirty() {
+                return self.advance(width);
+            }
+
+            if let Event::Escape = e {
+                self.cancel();
+                return Ok(true);
+            }
+
+            return Ok(dirty);
+        }
+
+        if self.pending.is_some() {
+            match e {
+                Event::Input(c) if c == "y" => {
+                    self.saving = true;
+                    self.child.update(&Event::Save, width)?;
+
+                    if !self.document().is_dirty() {
+                        return self.advance(width);
+                    }
+                }
+                Event::Input(c) if c == "n" => {
+                    // the document is discarded by closing its tab, which
+                    // advance does itself for a pending close
+                    if let Some(Action::Exit) = self.pending {
+                        self.child.update(&Event::Close, width)?;
+                    }
+
+                    return self.advance(width);
+                }
+                Event::Input(c) if c == "c" => self.cancel(),
+                Event::Escape => self.cancel(),
+                _ => return Ok(false),
+            }
+
+            return Ok(true);
+        }
+
+        match e {
+            Event::Close if self.document().is_dirty() => {
+                self.pending = Some(Action::Close);
+                Ok(true)
+            }
+            Event::Exit => {
+                if self.child.select_modified() {
+                    self.pending = Some(Action::Exit);
+                } else {
+                    self.quit = true;
+                }
+
+                Ok(true)
+            }
+            _ => self.child.update(e, width),
+        }
+    }
+
+    fn render(&mut self, width: usize, height: usize) -> Window {
+        if self.pending.is_none() || self.saving {
+            return self.child.render(width, height);
+        }
+
+        let mut child_window = self.child.render(width, height - 1);
+
+        let footer = line(
+            &Style {
+                foreground: 7,
+                background: 12,
+                decoration: vec![Decoration::Reverse],
+            },
+            &format!(
+                " Save changes to {}? (y)es (n)o (c)ancel ",
+                self.document().name()
+            ),
+            width,
+        );
+
+        child_window.lines.push(footer);
+
+        child_window
+    }
+
+    fn document(&mut self) -> &mut Document {
+        self.child.document()
+    }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
+}
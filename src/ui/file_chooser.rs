@@ -2,35 +2,29 @@ use std::io;
 
 use crate::document::Cursor;
 use crate::document::Document;
-use crate::style::styled;
+use crate::path;
+use crate::style::line;
+use crate::style::Decoration;
+use crate::style::Line;
 use crate::style::Style;
 use crate::terminal::Event;
+use crate::ui::prompt::Prompt;
 use crate::ui::Component;
 use crate::ui::Window;
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 enum Selection {
-    Open(String),
-    Save(String),
-}
-
-fn extend_selection(selection: &Selection, value: String) -> Selection {
-    match selection {
-        Selection::Open(s) => Selection::Open(format!("{}{}", s, value)),
-        Selection::Save(s) => Selection::Save(format!("{}{}", s, value)),
-    }
-}
-
-fn get_selection(selection: &Selection) -> &String {
-    match selection {
-        Selection::Open(s) => s,
-        Selection::Save(s) => s,
-    }
+    Open,
+    Save,
 }
 
 pub struct FileChooser {
     child: Box<dyn Component>,
     selection: Option<Selection>,
+    prompt: Prompt,
+    // Entries matching the path being typed, shown above the prompt once
+    // Tab has been pressed.
+    listing: Option<Vec<String>>,
 }
 
 impl FileChooser {
@@ -38,59 +32,136 @@ impl FileChooser {
         Box::new(FileChooser {
             child: child,
             selection: None,
+            prompt: Prompt::new(),
+            listing: None,
         })
     }
+
+    fn choose(&mut self, selection: Selection) {
+        self.prompt.open(match selection {
+            Selection::Open => "OPEN",
+            Selection::Save => "SAVE AS",
+        });
+        self.selection = Some(selection);
+    }
+
+    fn dismiss(&mut self) {
+        self.prompt.close();
+        self.selection = None;
+        self.listing = None;
+    }
+
+    fn complete(&mut self) {
+        let input = self.prompt.text();
+
+        if let Some(completed) = path::complete(&input, &path::completions(&input)) {
+            self.prompt.set_text(&completed);
+        }
+
+        self.listing = Some(path::completions(&self.prompt.text()));
+    }
+
+    fn save(&mut self) -> io::Result<()> {
+        let filename = self.document().name();
+        self.document()
+            .save()
+            .map_err(|e| path::describe(&filename, e))
+    }
+
+    // Lines listing the completions, at most `max` of them.
+    fn render_listing(&self, width: usize, max: usize) -> Vec<Line> {
+        let names = match &self.listing {
+            Some(names) => names,
+            None => return vec![],
+        };
+
+        let style = Style {
+            foreground: 7,
+            background: 236,
+            decoration: vec![Decoration::Reverse],
+        };
+
+        let shown = if names.len() > max {
+            max.saturating_sub(1)
+        } else {
+            names.len()
+        };
+
+        let mut lines: Vec<Line> = names[..shown]
+            .iter()
+            .map(|name| line(&style, &format!(" {}", name), width))
+            .collect();
+
+        if shown < names.len() && max > 0 {
+            let more = format!(" ... {} more", names.len() - shown);
+            lines.push(line(&style, &more, width));
+        }
+
+        lines
+    }
 }
 
 impl Component for FileChooser {
     fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
         let mut dirty = true;
-        if let Some(selection) = &self.selection.clone() {
+        if let Some(selection) = self.selection {
             match &e {
-                Event::Input(c) => {
-                    self.selection = Some(extend_selection(&selection, c.to_string()));
-                }
                 Event::Enter => {
-                    let filename = get_selection(selection);
+                    let filename = path::expand_home(&self.prompt.text());
+
+                    if !filename.is_empty() {
+                        self.prompt.remember();
 
-                    if filename.len() != 0 {
                         match selection {
-                            Selection::Open(_) => {
-                                // TODO: handle if file is already open
-                                dirty = self.child.update(&Event::New, width)?;
-                                self.document().open(filename.clone())?;
-                                self.selection = None;
+                            Selection::Open => {
+                                dirty = self.child.update(&Event::OpenFile(filename), width)?;
+                                self.dismiss();
                             }
-                            Selection::Save(_) => {
-                                self.document().set_filename(filename.clone());
-                                self.document().save()?;
-                                self.selection = None;
+                            Selection::Save => {
+                                path::check_writable(&filename)?;
+
+                                self.document()
+                                    .save_as(filename.clone())
+                                    .map_err(|e| path::describe(&filename, e))?;
+                                self.dismiss();
                             }
                         }
                     }
                 }
+                Event::Tab => {
+                    self.complete();
+                }
                 Event::Escape => {
-                    self.selection = None;
+                    self.dismiss();
+                }
+                Event::Close => {
+                    self.dismiss();
+                    dirty = self.child.update(e, width)?;
                 }
                 _ => {
-                    return Ok(false);
+                    if !self.prompt.update(e) {
+                        return Ok(false);
+                    }
+
+                    if self.listing.is_some() {
+                        self.listing = Some(path::completions(&self.prompt.text()));
+                    }
                 }
             }
 
             Ok(dirty)
         } else {
             match &e {
-                // TODO: handle close events to prompt for save
                 Event::Open => {
-                    self.selection = Some(Selection::Open(String::new()));
+                    self.choose(Selection::Open);
                 }
                 Event::Save => {
                     match self.document().filename {
                         Some(_) => {
-                            self.document().save()?;
+                            self.save()?;
                         }
                         None => {
-                            self.selection = Some(Selection::Save(String::new()));
+                            self.choose(Selection::Save);
                         }
                     };
                 }
@@ -108,37 +179,27 @@ impl Component for FileChooser {
             return self.child.render(width, height);
         }
 
-        let mut child_window = self.child.render(width, height - 1);
-        let mut status = String::new();
-
-        if let Some(selection) = &self.selection {
-            status = match selection {
-                Selection::Open(s) => format!("OPEN: {}", s),
-                Selection::Save(s) => format!("SAVE AS: {}", s),
-            };
-        }
-
-        let footer = styled(
-            &Style {
-                foreground: 7,
-                background: 12,
-                decoration: vec![],
-            },
-            &format!(" {} ", status),
-        );
+        let listing = self.render_listing(width, (height - 1) / 2);
+        let mut child_window = self.child.render(width, height - 1 - listing.len());
+        let (footer, x) = self.prompt.render(width);
 
+        child_window.lines.extend(listing);
         child_window.lines.push(footer);
 
         Window {
-            lines: child_window.lines,
             cursor: Cursor {
-                x: child_window.cursor.x,
-                y: child_window.cursor.y,
+                x,
+                y: child_window.lines.len() - 1,
             },
+            lines: child_window.lines,
         }
     }
 
     fn document(&mut self) -> &mut Document {
         self.child.document()
     }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
 }
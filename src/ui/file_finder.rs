@@ -0,0 +1,122 @@
+use std::io;
+use std::path::Path;
+
+use crate::document::Cursor;
+use crate::document::Document;
+use crate::fuzzy;
+use crate::terminal::Event;
+use crate::ui::list::List;
+use crate::ui::prompt::Prompt;
+use crate::ui::Component;
+use crate::ui::Window;
+use crate::walk;
+
+// Walking stops after this many files so a huge directory can't hang the
+// editor.
+const MAX_FILES: usize = 50000;
+const MAX_MATCHES: usize = 100;
+
+// Lists the files under the working directory that fuzzy match what is
+// typed, and opens the selected one.
+pub struct FileFinder {
+    child: Box<dyn Component>,
+    prompt: Prompt,
+    files: Vec<String>,
+    matches: List,
+}
+
+impl FileFinder {
+    pub fn new(child: Box<dyn Component>) -> Box<FileFinder> {
+        Box::new(FileFinder {
+            child,
+            prompt: Prompt::new(),
+            files: vec![],
+            matches: List::new(),
+        })
+    }
+
+    fn open(&mut self) {
+        self.files = walk::walk(Path::new("."), MAX_FILES);
+        self.prompt.open("FILE");
+        self.search();
+    }
+
+    fn close(&mut self) {
+        self.prompt.close();
+        self.files = vec![];
+        self.matches.set_items(vec![]);
+    }
+
+    fn search(&mut self) {
+        self.matches
+            .set_items(fuzzy::rank(&self.prompt.text(), &self.files, MAX_MATCHES));
+    }
+}
+
+impl Component for FileFinder {
+    fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
+        if let Event::FindFile = e {
+            self.open();
+            return Ok(true);
+        }
+
+        if !self.prompt.is_open() {
+            return self.child.update(e, width);
+        }
+
+        match e {
+            Event::Enter => {
+                if let Some(filename) = self.matches.selected_item().cloned() {
+                    self.close();
+                    return self.child.update(&Event::OpenFile(filename), width);
+                }
+            }
+            Event::Escape => {
+                self.close();
+            }
+            Event::Close => {
+                self.close();
+                return self.child.update(e, width);
+            }
+            _ if self.matches.update(e) => {}
+            _ => {
+                if !self.prompt.update(e) {
+                    return Ok(false);
+                }
+
+                self.search();
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn render(&mut self, width: usize, height: usize) -> Window {
+        if !self.prompt.is_open() {
+            return self.child.render(width, height);
+        }
+
+        let list = self.matches.render(width, (height - 1) / 2);
+        let mut child_window = self.child.render(width, height - 1 - list.len());
+        let (footer, x) = self.prompt.render(width);
+
+        child_window.lines.extend(list);
+        child_window.lines.push(footer);
+
+        Window {
+            cursor: Cursor {
+                x,
+                y: child_window.lines.len() - 1,
+            },
+            lines: child_window.lines,
+        }
+    }
+
+    fn document(&mut self) -> &mut Document {
+        self.child.document()
+    }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
+}
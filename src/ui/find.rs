@@ -1,97 +1,650 @@
+use unicode_width::UnicodeWidthStr;
+
 use std::io;
+use std::io::ErrorKind;
 
 use crate::document::Cursor;
 use crate::document::Document;
-use crate::style::styled;
+use crate::pattern::Flags;
+use crate::pattern::Pattern;
+use crate::style::fit;
+use crate::style::line;
+use crate::style::Decoration;
 use crate::style::Style;
 use crate::terminal::Event;
+use crate::ui::prompt::Prompt;
 use crate::ui::Component;
 use crate::ui::Window;
 
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    // asking for the text to find
+    Find,
+    // asking for the text to replace, and then what to replace it with
+    Replace,
+    With,
+    // asking whether to replace the selected match
+    Confirm,
+}
+
+// Finds text in the document, moving to the first match as it is typed, or
+// replaces it after asking about each match in turn, going from the cursor
+// to the end and then from the top back to where it started.
 pub struct Find {
     child: Box<dyn Component>,
-    search: Option<String>,
+    prompt: Prompt,
+    replacement: Prompt,
+    // kept between searches, and toggled while the prompt is open
+    flags: Flags,
+    stage: Option<Stage>,
+    // The matches with the text to replace each with while replacing, and
+    // the search, replacement and revision of the document they were found
+    // for, so they are only found again when one of those changes.
+    replacements: Vec<(Cursor, Cursor, String)>,
+    searched: Option<(String, Flags, Option<String>, usize)>,
+    // The match being confirmed and the text to replace it with.
+    current: Option<(Cursor, Cursor, String)>,
+    // Where replacing started, and whether it has gone past the end and
+    // started again from the top.
+    origin: Cursor,
+    wrapped: bool,
+    // Where the cursor and the view were when finding started, which
+    // Escape goes back to.
+    saved: (Cursor, usize),
+    // Shown in the footer: how many matches there are and which one the
+    // cursor is at, and whether the last search went round the document.
+    note: String,
+    notice: Option<&'static str>,
+}
+
+fn step(position: Cursor) -> Cursor {
+    Cursor {
+        x: position.x + 1,
+        y: position.y,
+    }
+}
+
+fn before(a: Cursor, b: Cursor) -> bool {
+    (a.y, a.x) < (b.y, b.x)
+}
+
+// Returns where `position`, which comes after `end`, moves to when the text
+// before `end` is replaced by text that ends at `new_end`.
+fn shift(position: Cursor, end: Cursor, new_end: Cursor) -> Cursor {
+    if position.y == end.y {
+        Cursor {
+            x: new_end.x + position.x - end.x,
+            y: new_end.y,
+        }
+    } else {
+        Cursor {
+            x: position.x,
+            y: position.y - end.y + new_end.y,
+        }
+    }
 }
 
 impl Find {
     pub fn new(child: Box<dyn Component>) -> Box<Find> {
         Box::new(Find {
             child: child,
-            search: None,
+            prompt: Prompt::new(),
+            replacement: Prompt::new(),
+            flags: Flags {
+                literal: true,
+                ..Flags::default()
+            },
+            stage: None,
+            replacements: vec![],
+            searched: None,
+            current: None,
+            origin: Cursor { x: 0, y: 0 },
+            wrapped: false,
+            saved: (Cursor { x: 0, y: 0 }, 0),
+            note: String::new(),
+            notice: None,
         })
     }
-}
 
-impl Component for Find {
-    fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
-        if let Event::Find = e {
-            self.search = Some(String::new());
-            return Ok(true);
-        }
-
-        if let Some(_) = self.search {
-            match &e {
-                Event::Input(c) => {
-                    self.search = Some(format!(
-                        "{}{}",
-                        self.search.clone().unwrap_or("".to_string()),
-                        c
-                    ))
+    // Names the search options that are on, as in "FIND [regex] [word]".
+    fn label(&self) -> String {
+        let mut label = match self.stage {
+            Some(Stage::Replace) => "REPLACE".to_string(),
+            _ => "FIND".to_string(),
+        };
+
+        for (on, name) in [
+            (!self.flags.literal, "regex"),
+            (self.flags.ignore_case, "ignore case"),
+            (self.flags.whole_word, "word"),
+        ] {
+            if on {
+                label.push_str(&format!(" [{}]", name));
+            }
+        }
+
+        label
+    }
+
+    fn pattern(&self) -> io::Result<Pattern> {
+        Pattern::new(&self.prompt.text(), self.flags)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, format!("invalid pattern: {}", e)))
+    }
+
+    fn open(&mut self, stage: Stage) {
+        self.stage = Some(stage);
+        self.saved = (self.child.document().cursor, self.child.window_offset());
+        self.prompt.open(&self.label());
+    }
+
+    // Moves to the first match from where finding started as the search
+    // changes, or back there if nothing matches.
+    fn search(&mut self) {
+        let (cursor, offset) = self.saved;
+
+        self.refresh();
+        // the view scrolls to the match from where it was to begin with
+        self.child.set_window_offset(offset);
+
+        let document = self.child.document();
+
+        match document.find_from(cursor) {
+            Some(true) => self.notice = Some("wrapped to top"),
+            Some(false) => {}
+            None => document.cursor = cursor,
+        }
+    }
+
+    fn restore(&mut self) {
+        let (cursor, offset) = self.saved;
+
+        self.child.document().cursor = cursor;
+        self.child.set_window_offset(offset);
+    }
+
+    fn close(&mut self) {
+        let document = self.child.document();
+        document.matches = vec![];
+        document.current_match = None;
+
+        self.stage = None;
+        self.replacements = vec![];
+        self.searched = None;
+        self.current = None;
+        self.prompt.close();
+        self.replacement.close();
+    }
+
+    // Finds the matches of the search again if it, the replacement or the
+    // document changed since they were last found. The matches are kept in
+    // the document to be highlighted, and while replacing they are kept
+    // here as well with the text to replace each with.
+    fn refresh(&mut self) {
+        let text = self.prompt.text();
+        let with = match self.stage {
+            Some(Stage::Confirm) => Some(self.replacement.text()),
+            _ => None,
+        };
+        let flags = self.flags;
+        let document = self.child.document();
+        let searched = Some((text, flags, with, document.revision()));
+
+        if self.searched == searched {
+            return;
+        }
+
+        let (text, _, with, _) = searched.as_ref().unwrap();
+        let pattern = match text.is_empty() {
+            true => None,
+            false => Pattern::new(text, flags).ok(),
+        };
+
+        match (pattern, with) {
+            (Some(pattern), Some(with)) => {
+                self.replacements = document.replacements(&pattern, with, !flags.literal);
+                document.matches = self
+                    .replacements
+                    .iter()
+                    .map(|(start, end, _)| (*start, *end))
+                    .collect();
+            }
+            (Some(pattern), None) => {
+                self.replacements = vec![];
+                document.matches = document.find_all(&pattern);
+            }
+            (None, _) => {
+                self.replacements = vec![];
+                document.matches = vec![];
+            }
+        }
+
+        self.searched = searched;
+    }
+
+    // Returns the matches left to confirm after `from` in the order they
+    // are visited, with the text to replace each with.
+    fn remaining(&self, from: Cursor) -> impl Iterator<Item = &(Cursor, Cursor, String)> {
+        let origin = self.origin;
+        let wrapped = self.wrapped;
+        let ahead = move |(start, _, _): &&(Cursor, Cursor, String)| !before(*start, from);
+        // matches before where replacing started are visited after wrapping
+        let wraps = move |(start, end, _): &&(Cursor, Cursor, String)| {
+            before(*start, origin) && !before(origin, *end)
+        };
+
+        let rest = self
+            .replacements
+            .iter()
+            .filter(move |r| !wrapped && !wraps(r) && ahead(r));
+        let wrapping = self
+            .replacements
+            .iter()
+            .filter(move |r| wraps(r) && (ahead(r) || !wrapped));
+
+        rest.chain(wrapping)
+    }
+
+    // Selects the next match to confirm, or stops replacing if there are
+    // none left.
+    fn next_match(&mut self, from: Cursor) {
+        self.refresh();
+
+        let next = self.remaining(from).next().cloned();
+
+        match next {
+            Some((start, end, text)) => {
+                if !self.wrapped && before(start, self.origin) {
+                    self.wrapped = true;
+                    self.notice = Some("wrapped to top");
+                }
+
+                let document = self.child.document();
+                document.deselect();
+                document.cursor = start;
+                self.current = Some((start, end, text));
+            }
+            None => self.close(),
+        }
+    }
+
+    fn start_replacing(&mut self) -> io::Result<()> {
+        self.pattern()?;
+        self.stage = Some(Stage::Confirm);
+        self.origin = self.child.document().cursor;
+        self.wrapped = false;
+        self.next_match(self.origin);
+
+        Ok(())
+    }
+
+    // Highlights the matches of the search and notes in the footer how many
+    // there are and which is the current one.
+    fn highlight(&mut self) {
+        self.refresh();
+
+        let text = self.prompt.text();
+        let searching = !text.is_empty() && Pattern::new(&text, self.flags).is_ok();
+        let document = self.child.document();
+
+        // the match being confirmed, or else the one at the cursor
+        let current = match &self.current {
+            Some((start, _, _)) => *start,
+            None => document.cursor,
+        };
+
+        document.current_match = document
+            .matches
+            .binary_search_by(|(start, _)| (start.y, start.x).cmp(&(current.y, current.x)))
+            .ok();
+
+        self.note = match (document.current_match, document.matches.len()) {
+            _ if !searching => String::new(),
+            (_, 0) => "no matches".to_string(),
+            (Some(i), count) => format!("{}/{}", i + 1, count),
+            (None, 1) => "1 match".to_string(),
+            (None, count) => format!("{} matches", count),
+        };
+
+        if let Some(notice) = self.notice {
+            self.note = format!("{}  {}", notice, self.note);
+        }
+
+        self.prompt.set_note(&self.note);
+        self.replacement.set_note(&self.note);
+    }
+
+    fn respond(&mut self, stage: Stage, e: &Event, width: usize) -> io::Result<bool> {
+        match (stage, e) {
+            (_, Event::Close) => {
+                self.close();
+                return self.child.update(e, width);
+            }
+            (Stage::Find, Event::Escape) => {
+                // a cancelled search can still be recalled
+                self.prompt.remember();
+                self.restore();
+                self.close();
+            }
+            (_, Event::Escape) => self.close(),
+            (Stage::Confirm, Event::Input(answer)) => return Ok(self.confirm(answer)),
+            (Stage::Confirm, _) => return Ok(false),
+            (Stage::With, Event::Enter) => {
+                self.replacement.remember();
+                self.start_replacing()?;
+            }
+            (Stage::With, _) => return Ok(self.replacement.update(e)),
+            (Stage::Find, Event::Enter) => {
+                self.prompt.remember();
+                self.close();
+            }
+            // finding again with nothing typed recalls the last search
+            (Stage::Find, Event::Find) if self.prompt.text().is_empty() => {
+                self.prompt.update(&Event::Up);
+            }
+            (Stage::Find, Event::Find) => {
+                self.pattern()?;
+                self.refresh();
+
+                if self.child.document().find_next() == Some(true) {
+                    self.notice = Some("wrapped to top");
                 }
-                Event::Enter => {
-                    let search = self.search.clone().unwrap_or(String::new());
+            }
+            (Stage::Find, Event::FindPrev) => {
+                self.pattern()?;
+                self.refresh();
 
-                    if search.len() != 0 {
-                        self.child.document().find_next(search);
-                    }
+                if self.child.document().find_prev() == Some(true) {
+                    self.notice = Some("wrapped to bottom");
                 }
-                Event::Escape => {
-                    self.search = None;
+            }
+            (_, Event::Enter) if self.prompt.text().is_empty() => {}
+            (Stage::Replace, Event::Enter) => {
+                self.prompt.remember();
+                // reports a bad pattern before asking for the replacement
+                self.pattern()?;
+                self.stage = Some(Stage::With);
+                self.replacement.open("WITH");
+            }
+            (_, Event::ToggleRegex) => self.flags.literal = !self.flags.literal,
+            (_, Event::ToggleCase) => self.flags.ignore_case = !self.flags.ignore_case,
+            (_, Event::ToggleWord) => self.flags.whole_word = !self.flags.whole_word,
+            _ => {
+                return Ok(self.prompt.update(e));
+            }
+        }
+
+        self.prompt.set_label(&self.label());
+
+        Ok(true)
+    }
+
+    fn confirm(&mut self, answer: &str) -> bool {
+        let (start, end, text) = match self.current.clone() {
+            Some(current) => current,
+            None => return false,
+        };
+
+        match answer {
+            "y" => {
+                let new_end = self.child.document().replace_range(start, end, &text);
+
+                if self.wrapped {
+                    self.origin = shift(self.origin, end, new_end);
                 }
-                _ => {
-                    return Ok(false);
+
+                // an empty match would be found again at the same place
+                self.next_match(if start == end { step(new_end) } else { new_end });
+            }
+            "n" => self.next_match(if start == end { step(end) } else { end }),
+            "a" => {
+                self.refresh();
+
+                let mut replacements: Vec<_> = self.remaining(start).cloned().collect();
+                replacements.sort_by_key(|(start, _, _)| (start.y, start.x));
+
+                self.child.document().replace_all(&replacements);
+                self.close();
+            }
+            "q" => self.close(),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+impl Component for Find {
+    fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
+        match e {
+            Event::Find if self.stage != Some(Stage::Find) => {
+                self.close();
+                self.open(Stage::Find);
+                self.highlight();
+                return Ok(true);
+            }
+            Event::Replace => {
+                if self.child.document().readonly {
+                    return Err(io::Error::new(
+                        ErrorKind::PermissionDenied,
+                        format!("{} is open read-only", self.child.document().name()),
+                    ));
                 }
+
+                self.close();
+                self.open(Stage::Replace);
+                self.highlight();
+                return Ok(true);
             }
+            _ => {}
+        }
+
+        let stage = match self.stage {
+            Some(stage) => stage,
+            None => return self.child.update(e, width),
+        };
 
-            return Ok(true);
+        let search = (self.prompt.text(), self.flags);
+
+        self.notice = None;
+        let result = self.respond(stage, e, width);
+
+        if self.stage == Some(Stage::Find) && search != (self.prompt.text(), self.flags) {
+            self.search();
         }
 
-        self.child.update(e, width)
+        if self.stage.is_some() {
+            self.highlight();
+        }
+
+        result
     }
 
     fn render(&mut self, width: usize, height: usize) -> Window {
-        if let None = self.search {
-            return self.child.render(width, height);
-        }
+        let stage = match self.stage {
+            Some(stage) => stage,
+            None => return self.child.render(width, height),
+        };
 
         let mut child_window = self.child.render(width, height - 1);
 
-        let status = match &self.search {
-            Some(s) => format!("FIND: {}", s),
-            None => String::new(),
-        };
+        if stage == Stage::Confirm {
+            let footer = line(
+                &Style {
+                    foreground: 7,
+                    background: 12,
+                    decoration: vec![Decoration::Reverse],
+                },
+                &format!(
+                    "{}{}",
+                    fit(
+                        " Replace this match? (y)es (n)o (a)ll (q)uit ",
+                        width.saturating_sub(self.note.width() + 1)
+                    ),
+                    self.note
+                ),
+                width,
+            );
 
-        let footer = styled(
-            &Style {
-                foreground: 7,
-                background: 12,
-                decoration: vec![],
-            },
-            &format!(" {} ", status),
-        );
+            child_window.lines.push(footer);
+
+            return child_window;
+        }
+
+        let (footer, x) = match stage {
+            Stage::With => self.replacement.render(width),
+            _ => self.prompt.render(width),
+        };
 
         child_window.lines.push(footer);
 
         Window {
-            lines: child_window.lines,
             cursor: Cursor {
-                x: child_window.cursor.x,
-                y: child_window.cursor.y,
+                x,
+                y: child_window.lines.len() - 1,
             },
+            lines: child_window.lines,
         }
     }
 
     fn document(&mut self) -> &mut Document {
         self.child.document()
     }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::Cursor;
+    use crate::document::Document;
+    use crate::terminal::Event;
+    use crate::ui::find::Find;
+    use crate::ui::Component;
+    use crate::ui::TextArea;
+
+    fn replace(text: &str, cursor: Cursor, search: &str, with: &str, answers: &str) -> String {
+        let mut document = Document::blank();
+        document.paste(text);
+        document.cursor = cursor;
+
+        let mut find = Find::new(TextArea::new(document));
+        let mut send = |e: Event| find.update(&e, 80).unwrap();
+
+        send(Event::Replace);
+        send(Event::Input(search.to_string()));
+        send(Event::Enter);
+        send(Event::Input(with.to_string()));
+        send(Event::Enter);
+
+        for answer in answers.chars() {
+            send(Event::Input(answer.to_string()));
+        }
+
+        let document = find.document();
+        let end = Cursor {
+            x: document.line_len(document.line_count() - 1),
+            y: document.line_count() - 1,
+        };
+        document.text(Cursor { x: 0, y: 0 }, end)
+    }
+
+    #[test]
+    fn notes_matches_and_wrapping() {
+        let mut document = Document::blank();
+        document.paste("a a\na");
+
+        let mut find = Find::new(TextArea::new(document));
+
+        find.update(&Event::Find, 80).unwrap();
+        find.update(&Event::Input("a".to_string()), 80).unwrap();
+        assert_eq!(find.note, "wrapped to top  1/3");
+        assert_eq!(find.document().matches.len(), 3);
+
+        find.update(&Event::Find, 80).unwrap();
+        assert_eq!(find.note, "2/3");
+        assert_eq!(find.document().current_match, Some(1));
+        find.update(&Event::FindPrev, 80).unwrap();
+        find.update(&Event::FindPrev, 80).unwrap();
+        assert_eq!(find.note, "wrapped to bottom  3/3");
+
+        find.update(&Event::Enter, 80).unwrap();
+        assert!(find.document().matches.is_empty());
+        assert!(find.document().cursor == Cursor { x: 0, y: 1 });
+    }
+
+    #[test]
+    fn finds_while_typing() {
+        let mut document = Document::blank();
+        document.paste("ab\nac\nab");
+        document.cursor = Cursor { x: 1, y: 0 };
+
+        let mut find = Find::new(TextArea::new(document));
+        let mut send = |e: Event| find.update(&e, 80).unwrap();
+
+        send(Event::Find);
+        send(Event::Input("a".to_string()));
+        send(Event::Input("c".to_string()));
+        assert!(find.document().cursor == Cursor { x: 0, y: 1 });
+
+        // the cursor stays put while nothing matches
+        find.update(&Event::Input("x".to_string()), 80).unwrap();
+        assert!(find.document().cursor == Cursor { x: 1, y: 0 });
+        find.update(&Event::Backspace, 80).unwrap();
+        assert!(find.document().cursor == Cursor { x: 0, y: 1 });
+
+        // Escape goes back to where finding started
+        find.update(&Event::Escape, 80).unwrap();
+        assert!(find.document().cursor == Cursor { x: 1, y: 0 });
+
+        // and finding with nothing typed recalls the last search
+        find.update(&Event::Find, 80).unwrap();
+        find.update(&Event::Find, 80).unwrap();
+        assert_eq!(find.prompt.text(), "ac");
+        assert!(find.document().cursor == Cursor { x: 0, y: 1 });
+    }
+
+    #[test]
+    fn counts_again_after_replacing() {
+        let mut document = Document::blank();
+        document.paste("a a a");
+        document.cursor = Cursor { x: 0, y: 0 };
+
+        let mut find = Find::new(TextArea::new(document));
+        let mut send = |e: Event| find.update(&e, 80).unwrap();
+
+        send(Event::Replace);
+        send(Event::Input("a".to_string()));
+        send(Event::Enter);
+        send(Event::Input("b".to_string()));
+        send(Event::Enter);
+        send(Event::Input("n".to_string()));
+        assert_eq!(find.note, "2/3");
+
+        // the matches are found again once the document changes
+        find.update(&Event::Input("y".to_string()), 80).unwrap();
+        assert_eq!(find.note, "2/2");
+        assert_eq!(find.document().matches.len(), 2);
+    }
+
+    #[test]
+    fn confirms_each_replacement() {
+        let start = Cursor { x: 0, y: 0 };
+        let middle = Cursor { x: 2, y: 1 };
+
+        assert_eq!(replace("aa\naaa", start, "a", "b", "ynyq"), "ba\nbaa");
+        assert_eq!(replace("aa\naaa", start, "a", "b", "nya"), "ab\nbbb");
+
+        // replacing goes on from the top and stops where it started
+        assert_eq!(
+            replace("aa\naaa", middle, "a", "bb", "yyyyy"),
+            "bbbb\nbbbbbb"
+        );
+        assert_eq!(replace("aa\naaa", middle, "a", "b", "nna"), "ab\nbba");
+
+        // text put in by a replacement isn't replaced again, and replacing
+        // ends after the last match so the next key is typed
+        assert_eq!(replace("a", start, "a", "aa", "yx"), "aax");
+    }
 }
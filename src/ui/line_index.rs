@@ -0,0 +1,207 @@
+use crate::document::Document;
+use crate::document::Row;
+
+const CHUNK: usize = 1024;
+
+// The number of display lines each row of a document wraps to, kept in
+// chunks with their totals so the display line a row starts on is found
+// without adding up every row before it.
+struct Chunk {
+    heights: Vec<usize>,
+    total: usize,
+}
+
+impl Chunk {
+    fn new(heights: Vec<usize>) -> Chunk {
+        let total = heights.iter().sum();
+
+        Chunk { heights, total }
+    }
+}
+
+pub struct LineIndex {
+    width: usize,
+    chunks: Vec<Chunk>,
+}
+
+// Returns how many display lines `line` wraps to. Lines too short to wrap
+// aren't split into graphemes, as no grapheme is wider than its bytes but a
+// tab.
+fn height(line: &str, width: usize) -> usize {
+    let tabs = line.bytes().filter(|b| *b == b'\t').count();
+
+    if line.len() + 3 * tabs < width {
+        return 1;
+    }
+
+    Row::new(line.to_string()).layout(width).last().unwrap().0 + 1
+}
+
+impl LineIndex {
+    pub fn new() -> LineIndex {
+        LineIndex {
+            width: 0,
+            chunks: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.heights.len()).sum()
+    }
+
+    // Returns the number of display lines in the document.
+    pub fn total(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.total).sum()
+    }
+
+    // Returns the display line row `y` starts on.
+    pub fn start_of(&self, y: usize) -> usize {
+        let mut line = 0;
+        let mut first = 0;
+
+        for chunk in &self.chunks {
+            if y < first + chunk.heights.len() {
+                return line + chunk.heights[..y - first].iter().sum::<usize>();
+            }
+
+            line += chunk.total;
+            first += chunk.heights.len();
+        }
+
+        line
+    }
+
+    // Returns the row drawn on display line `line` and the display line it
+    // starts on, or the number of rows and display lines past the end.
+    pub fn row_at(&self, line: usize) -> (usize, usize) {
+        let mut start = 0;
+        let mut first = 0;
+
+        for chunk in &self.chunks {
+            if line < start + chunk.total {
+                for (i, height) in chunk.heights.iter().enumerate() {
+                    if line < start + height {
+                        return (first + i, start);
+                    }
+
+                    start += height;
+                }
+            }
+
+            start += chunk.total;
+            first += chunk.heights.len();
+        }
+
+        (first, start)
+    }
+
+    // Replaces the heights of rows `start` to `end` with `heights`.
+    fn splice(&mut self, start: usize, end: usize, heights: Vec<usize>) {
+        // the chunks holding the rows, and the first row of the first one
+        let mut from = 0;
+        let mut first = 0;
+
+        while from < self.chunks.len() && first + self.chunks[from].heights.len() <= start {
+            first += self.chunks[from].heights.len();
+            from += 1;
+        }
+
+        let mut to = from;
+        let mut last = first;
+
+        while to < self.chunks.len() && last < end.max(start + 1) {
+            last += self.chunks[to].heights.len();
+            to += 1;
+        }
+
+        // small chunks left by earlier edits are merged with their neighbours
+        if from > 0 && last - first < CHUNK / 2 {
+            from -= 1;
+            first -= self.chunks[from].heights.len();
+        }
+
+        if to < self.chunks.len() && last - first < CHUNK / 2 {
+            to += 1;
+        }
+
+        let mut rows: Vec<usize> = self.chunks[from..to]
+            .iter()
+            .flat_map(|chunk| chunk.heights.iter().copied())
+            .collect();
+        rows.splice(start - first..end - first, heights);
+
+        let chunks: Vec<Chunk> = rows
+            .chunks(CHUNK)
+            .map(|heights| Chunk::new(heights.to_vec()))
+            .collect();
+        self.chunks.splice(from..to, chunks);
+    }
+
+    // Catches up with the edits made to the document since the last time,
+    // or measures every row again when the width has changed.
+    pub fn update(&mut self, document: &mut Document, width: usize) {
+        let changed = document.take_changed_rows();
+        let (first, unchanged) = match changed {
+            _ if width != self.width => (0, 0),
+            Some(changed) => changed,
+            None => return,
+        };
+
+        let end = self.len().saturating_sub(unchanged).max(first);
+        let new_end = document.line_count().saturating_sub(unchanged).max(first);
+        let heights = document
+            .rows_text(first, new_end)
+            .split_terminator('\n')
+            .map(|line| height(line, width))
+            .collect();
+
+        if width != self.width {
+            self.width = width;
+            self.chunks = vec![];
+        }
+
+        self.splice(first, end.min(self.len()), heights);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::Cursor;
+    use crate::document::Document;
+    use crate::ui::line_index::LineIndex;
+
+    fn heights(index: &LineIndex) -> Vec<usize> {
+        (0..index.len())
+            .map(|y| index.start_of(y + 1) - index.start_of(y))
+            .collect()
+    }
+
+    #[test]
+    fn follows_edits() {
+        let mut document = Document::blank();
+        let mut index = LineIndex::new();
+
+        document.paste(&"a\n".repeat(3000));
+        index.update(&mut document, 4);
+        assert_eq!(index.len(), 3001);
+        assert_eq!(index.total(), 3001);
+
+        document.cursor = Cursor { x: 1, y: 2000 };
+        document.paste("bcdef\ngh");
+        document.cursor = Cursor { x: 0, y: 10 };
+        document.delete_prev();
+        index.update(&mut document, 4);
+
+        let mut expected = vec![1; 3001];
+        expected[1999] = 2;
+        assert_eq!(heights(&index), expected);
+        assert_eq!(index.start_of(2000), 2001);
+        assert_eq!(index.row_at(2000), (1999, 1999));
+        assert_eq!(index.row_at(2001), (2000, 2001));
+        assert_eq!(index.row_at(9000), (3001, 3002));
+
+        // a new width measures every row again
+        index.update(&mut document, 2);
+        assert_eq!(index.total(), 3006);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::style::line;
+use crate::style::Decoration;
+use crate::style::Line;
+use crate::style::Style;
+use crate::terminal::Event;
+
+// A list of lines drawn above a prompt, one of which is selected.
+pub struct List {
+    items: Vec<String>,
+    selected: usize,
+    // how many items were drawn last, which paging moves by
+    rows: usize,
+}
+
+impl List {
+    pub fn new() -> List {
+        List {
+            items: vec![],
+            selected: 0,
+            rows: 1,
+        }
+    }
+
+    // Replaces the items and selects the first.
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.selected = 0;
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        match self.items.is_empty() {
+            true => None,
+            false => Some(self.selected),
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<&String> {
+        self.items.get(self.selected)
+    }
+
+    // Moves the selection, returning false for events it doesn't handle.
+    pub fn update(&mut self, e: &Event) -> bool {
+        let last = self.items.len().saturating_sub(1);
+        let rows = self.rows.max(1);
+
+        match e {
+            Event::Up => self.selected = self.selected.saturating_sub(1),
+            Event::Down => self.selected = (self.selected + 1).min(last),
+            Event::PageUp => self.selected = self.selected.saturating_sub(rows),
+            Event::PageDown => self.selected = (self.selected + rows).min(last),
+            _ => return false,
+        }
+
+        true
+    }
+
+    // Draws up to `rows` items, keeping the selected one in view when it is
+    // below them.
+    pub fn render(&mut self, width: usize, rows: usize) -> Vec<Line> {
+        let style = Style {
+            foreground: 7,
+            background: 236,
+            decoration: vec![Decoration::Reverse],
+        };
+
+        let rows = rows.min(self.items.len());
+        self.rows = rows;
+
+        if rows == 0 {
+            return vec![];
+        }
+        let first = (self.selected + 1).saturating_sub(rows);
+
+        self.items[first..first + rows]
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let text = format!(" {}", item);
+
+                if first + i == self.selected {
+                    line(&style.inverted(), &text, width)
+                } else {
+                    line(&style, &text, width)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal::Event;
+    use crate::ui::list::List;
+
+    #[test]
+    fn keeps_selection_in_view() {
+        let mut list = List::new();
+        assert_eq!(list.selected(), None);
+        assert!(list.render(10, 2).is_empty());
+
+        list.set_items((1..=5).map(|i| i.to_string()).collect());
+        assert_eq!(list.selected(), Some(0));
+
+        list.update(&Event::Down);
+        list.update(&Event::Down);
+        list.update(&Event::Down);
+        assert_eq!(list.selected_item(), Some(&"4".to_string()));
+
+        let lines = list.render(10, 2);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1][1].grapheme, "4");
+        assert!(lines[1][1].style == lines[0][1].style.inverted());
+
+        // paging moves by the rows drawn
+        list.update(&Event::PageUp);
+        assert_eq!(list.selected(), Some(1));
+        list.update(&Event::PageDown);
+        list.update(&Event::PageDown);
+        assert_eq!(list.selected(), Some(4));
+        assert!(!list.update(&Event::Enter));
+    }
+}
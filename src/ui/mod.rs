@@ -2,22 +2,34 @@ use std::io;
 
 use crate::document::Cursor;
 use crate::document::Document;
+use crate::style::Line;
 use crate::terminal::Event;
 
+mod clipboard;
+mod confirm;
 mod file_chooser;
+mod file_finder;
 mod find;
+mod line_index;
+mod list;
+mod project_search;
+mod prompt;
 mod status;
 mod tabs;
 mod text_area;
 
+pub use clipboard::Clipboard;
+pub use confirm::Confirm;
 pub use file_chooser::FileChooser;
+pub use file_finder::FileFinder;
 pub use find::Find;
+pub use project_search::ProjectSearch;
 pub use status::Status;
 pub use tabs::Tabs;
 pub use text_area::TextArea;
 
 pub struct Window {
-    pub lines: Vec<String>,
+    pub lines: Vec<Line>,
     pub cursor: Cursor,
 }
 
@@ -25,4 +37,29 @@ pub trait Component {
     fn update(&mut self, e: &Event, width: usize) -> io::Result<bool>;
     fn render(&mut self, width: usize, height: usize) -> Window;
     fn document(&mut self) -> &mut Document;
+
+    // The component this one is wrapped around, which the methods below are
+    // passed on to unless it handles them itself. Only the text area has
+    // none.
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        None
+    }
+
+    // Selects a document with unsaved changes, returning false if there are
+    // none.
+    fn select_modified(&mut self) -> bool {
+        self.child().is_some_and(|child| child.select_modified())
+    }
+
+    // The first display line on screen, which a search puts back when it
+    // is cancelled.
+    fn window_offset(&mut self) -> usize {
+        self.child().map_or(0, |child| child.window_offset())
+    }
+
+    fn set_window_offset(&mut self, offset: usize) {
+        if let Some(child) = self.child() {
+            child.set_window_offset(offset);
+        }
+    }
 }
@@ -0,0 +1,233 @@
+use std::io;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::document::Cursor;
+use crate::document::Document;
+use crate::pattern::Flags;
+use crate::pattern::Pattern;
+use crate::search;
+use crate::search::Hit;
+use crate::terminal::Event;
+use crate::ui::list::List;
+use crate::ui::prompt::Prompt;
+use crate::ui::Component;
+use crate::ui::Window;
+
+const MAX_HITS: usize = 1000;
+
+// Searches the files under `root` for what is typed when Enter is pressed,
+// and lists the matching lines. Enter again opens the selected line. The
+// results are kept for the next time it is opened.
+pub struct ProjectSearch {
+    child: Box<dyn Component>,
+    root: PathBuf,
+    prompt: Prompt,
+    flags: Flags,
+    hits: Vec<Hit>,
+    list: List,
+    // the search the hits are for
+    searched: Option<(String, Flags)>,
+}
+
+impl ProjectSearch {
+    pub fn new(child: Box<dyn Component>, root: PathBuf) -> Box<ProjectSearch> {
+        Box::new(ProjectSearch {
+            child,
+            root,
+            prompt: Prompt::new(),
+            flags: Flags {
+                literal: true,
+                ..Flags::default()
+            },
+            hits: vec![],
+            list: List::new(),
+            searched: None,
+        })
+    }
+
+    fn label(&self) -> String {
+        let mut label = "SEARCH FILES".to_string();
+
+        for (on, name) in [
+            (!self.flags.literal, "regex"),
+            (self.flags.ignore_case, "ignore case"),
+            (self.flags.whole_word, "word"),
+        ] {
+            if on {
+                label.push_str(&format!(" [{}]", name));
+            }
+        }
+
+        label
+    }
+
+    fn open(&mut self) {
+        self.prompt.open(&self.label());
+
+        if let Some((text, _)) = &self.searched {
+            self.prompt.set_text(text);
+        }
+
+        self.note();
+    }
+
+    fn note(&mut self) {
+        let note = match (&self.searched, self.hits.len()) {
+            (None, _) => String::new(),
+            (Some(_), 0) => "no matches".to_string(),
+            (Some(_), MAX_HITS) => format!("first {} lines", MAX_HITS),
+            (Some(_), 1) => "1 line".to_string(),
+            (Some(_), count) => format!("{} lines", count),
+        };
+
+        self.prompt.set_note(&note);
+    }
+
+    fn search(&mut self) -> io::Result<()> {
+        let text = self.prompt.text();
+        let pattern = Pattern::new(&text, self.flags).map_err(|e| {
+            io::Error::new(ErrorKind::InvalidInput, format!("invalid pattern: {}", e))
+        })?;
+
+        self.hits = search::search(&self.root, &pattern, MAX_HITS);
+        self.list.set_items(
+            self.hits
+                .iter()
+                .map(|hit| {
+                    let text = hit.text.trim().replace('\t', " ");
+                    format!("{}:{}: {}", hit.path, hit.line, text)
+                })
+                .collect(),
+        );
+        self.searched = Some((text, self.flags));
+        self.note();
+
+        Ok(())
+    }
+
+    fn open_hit(&mut self, width: usize) -> io::Result<bool> {
+        let hit = match self.list.selected() {
+            Some(i) => &self.hits[i],
+            None => return Ok(true),
+        };
+
+        // files under the working directory are opened by the paths listed
+        let path = self.root.join(&hit.path);
+        let path = path.strip_prefix(".").unwrap_or(&path);
+
+        let (line, column) = (hit.line, hit.column);
+        let event = Event::OpenFile(path.to_string_lossy().into_owned());
+        let result = self.child.update(&event, width);
+        self.prompt.close();
+        result?;
+
+        self.child.document().go_to(line, column);
+
+        Ok(true)
+    }
+}
+
+impl Component for ProjectSearch {
+    fn update(&mut self, e: &Event, width: usize) -> io::Result<bool> {
+        if let Event::SearchFiles = e {
+            self.open();
+            return Ok(true);
+        }
+
+        if !self.prompt.is_open() {
+            return self.child.update(e, width);
+        }
+
+        match e {
+            Event::Enter if self.prompt.text().is_empty() => {}
+            Event::Enter if self.searched != Some((self.prompt.text(), self.flags)) => {
+                self.search()?;
+            }
+            Event::Enter => return self.open_hit(width),
+            Event::Escape => self.prompt.close(),
+            Event::Close => {
+                self.prompt.close();
+                return self.child.update(e, width);
+            }
+            Event::ToggleRegex => self.flags.literal = !self.flags.literal,
+            Event::ToggleCase => self.flags.ignore_case = !self.flags.ignore_case,
+            Event::ToggleWord => self.flags.whole_word = !self.flags.whole_word,
+            _ if self.list.update(e) => {}
+            _ => return Ok(self.prompt.update(e)),
+        }
+
+        self.prompt.set_label(&self.label());
+
+        Ok(true)
+    }
+
+    fn render(&mut self, width: usize, height: usize) -> Window {
+        if !self.prompt.is_open() {
+            return self.child.render(width, height);
+        }
+
+        let list = self.list.render(width, (height - 1) / 2);
+        let mut child_window = self.child.render(width, height - 1 - list.len());
+        let (footer, x) = self.prompt.render(width);
+
+        child_window.lines.extend(list);
+        child_window.lines.push(footer);
+
+        Window {
+            cursor: Cursor {
+                x,
+                y: child_window.lines.len() - 1,
+            },
+            lines: child_window.lines,
+        }
+    }
+
+    fn document(&mut self) -> &mut Document {
+        self.child.document()
+    }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::Cursor;
+    use crate::document::Document;
+    use crate::fixture::Fixture;
+    use crate::terminal::Event;
+    use crate::ui::project_search::ProjectSearch;
+    use crate::ui::Component;
+    use crate::ui::Tabs;
+    use crate::ui::TextArea;
+
+    #[test]
+    fn opens_the_selected_line() {
+        let fixture = Fixture::new(
+            "project-search",
+            &[
+                ("notes.txt", "two\n"),
+                ("src/lib.rs", "fn one() {}\n\n    fn two() {}\n"),
+            ],
+        );
+        let child = Tabs::new(TextArea::new(Document::blank()));
+        let mut search = ProjectSearch::new(child, fixture.root.clone());
+        let mut send = |e: Event| search.update(&e, 80).unwrap();
+
+        send(Event::SearchFiles);
+        send(Event::Input("fn two".to_string()));
+        send(Event::Enter);
+        send(Event::Enter);
+
+        let document = search.document();
+        assert_eq!(document.name(), fixture.path("src/lib.rs"));
+        assert!(document.cursor == Cursor { x: 4, y: 2 });
+
+        // the results are kept for the next search
+        search.update(&Event::SearchFiles, 80).unwrap();
+        assert_eq!(search.prompt.text(), "fn two");
+        assert_eq!(search.hits.len(), 1);
+    }
+}
@@ -0,0 +1,289 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::style::fit;
+use crate::style::line;
+use crate::style::Decoration;
+use crate::style::Line;
+use crate::style::Style;
+use crate::terminal::Event;
+
+// A single line text input shown in the footer, used by the prompts that
+// ask for a search or a filename. Entries submitted with `remember` can be
+// recalled with up and down the next time the prompt is opened.
+pub struct Prompt {
+    label: String,
+    // shown at the right end of the footer
+    note: String,
+    graphemes: Vec<String>,
+    cursor: usize,
+    open: bool,
+    history: Vec<String>,
+    // The history entry being shown, or history.len() for the text that was
+    // being typed before browsing the history.
+    recalled: usize,
+    draft: Vec<String>,
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn graphemes(text: &str) -> Vec<String> {
+    text.graphemes(false).map(|g| g.to_string()).collect()
+}
+
+impl Prompt {
+    pub fn new() -> Prompt {
+        Prompt {
+            label: String::new(),
+            note: String::new(),
+            graphemes: vec![],
+            cursor: 0,
+            open: false,
+            history: vec![],
+            recalled: 0,
+            draft: vec![],
+        }
+    }
+
+    pub fn open(&mut self, label: &str) {
+        self.label = label.to_string();
+        self.note = String::new();
+        self.open = true;
+        self.recalled = self.history.len();
+        self.set_text("");
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.label = label.to_string();
+    }
+
+    pub fn set_note(&mut self, note: &str) {
+        self.note = note.to_string();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn text(&self) -> String {
+        self.graphemes.concat()
+    }
+
+    // Replaces the text and moves the cursor to its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.graphemes = graphemes(text);
+        self.cursor = self.graphemes.len();
+    }
+
+    // Adds the current text to the history, moving it to the end if it is
+    // already there.
+    pub fn remember(&mut self) {
+        let text = self.text();
+
+        if !text.is_empty() {
+            self.history.retain(|entry| *entry != text);
+            self.history.push(text);
+        }
+
+        self.recalled = self.history.len();
+    }
+
+    fn insert(&mut self, text: &str) {
+        let inserted = graphemes(text);
+        let count = inserted.len();
+
+        self.graphemes.splice(self.cursor..self.cursor, inserted);
+        self.cursor += count;
+    }
+
+    fn word_left(&self) -> usize {
+        let mut x = self.cursor;
+
+        while x > 0 && !is_word(&self.graphemes[x - 1]) {
+            x -= 1;
+        }
+
+        while x > 0 && is_word(&self.graphemes[x - 1]) {
+            x -= 1;
+        }
+
+        x
+    }
+
+    fn word_right(&self) -> usize {
+        let mut x = self.cursor;
+
+        while x < self.graphemes.len() && !is_word(&self.graphemes[x]) {
+            x += 1;
+        }
+
+        while x < self.graphemes.len() && is_word(&self.graphemes[x]) {
+            x += 1;
+        }
+
+        x
+    }
+
+    fn recall(&mut self, index: usize) {
+        if self.recalled == self.history.len() {
+            self.draft = self.graphemes.clone();
+        }
+
+        self.recalled = index;
+
+        if index == self.history.len() {
+            self.graphemes = self.draft.clone();
+            self.cursor = self.graphemes.len();
+        } else {
+            self.set_text(&self.history[index].clone());
+        }
+    }
+
+    // Applies an editing event to the text, returning false if the event
+    // isn't one the prompt handles.
+    pub fn update(&mut self, e: &Event) -> bool {
+        match e {
+            Event::Input(c) => self.insert(c),
+            Event::Paste(text) => self.insert(text.lines().next().unwrap_or("")),
+            Event::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.graphemes.remove(self.cursor);
+            }
+            Event::Delete if self.cursor < self.graphemes.len() => {
+                self.graphemes.remove(self.cursor);
+            }
+            Event::Backspace | Event::Delete => {}
+            Event::Left => self.cursor = self.cursor.saturating_sub(1),
+            Event::Right => self.cursor = (self.cursor + 1).min(self.graphemes.len()),
+            Event::Home => self.cursor = 0,
+            Event::End => self.cursor = self.graphemes.len(),
+            Event::WordLeft => self.cursor = self.word_left(),
+            Event::WordRight => self.cursor = self.word_right(),
+            Event::DeleteWordLeft => {
+                let start = self.word_left();
+                self.graphemes.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Event::DeleteWordRight => {
+                let end = self.word_right();
+                self.graphemes.drain(self.cursor..end);
+            }
+            Event::Up if self.recalled > 0 => self.recall(self.recalled - 1),
+            Event::Down if self.recalled < self.history.len() => self.recall(self.recalled + 1),
+            Event::Up | Event::Down => {}
+            _ => return false,
+        }
+
+        true
+    }
+
+    // Returns the footer line showing the prompt and the column of its
+    // cursor. Text that doesn't fit is scrolled to keep the cursor visible.
+    pub fn render(&self, width: usize) -> (Line, usize) {
+        let label = format!(" {}: ", self.label);
+        let note = match self.note.is_empty() {
+            true => String::new(),
+            false => format!(" {} ", self.note),
+        };
+        let available = width.saturating_sub(label.width() + note.width() + 1);
+        let widths: Vec<usize> = self.graphemes.iter().map(|g| g.width()).collect();
+
+        let mut start = 0;
+        while start < self.cursor && widths[start..self.cursor].iter().sum::<usize>() >= available {
+            start += 1;
+        }
+
+        let mut end = start;
+        let mut used = 0;
+        while end < self.graphemes.len() && used + widths[end] <= available {
+            used += widths[end];
+            end += 1;
+        }
+
+        let footer = line(
+            &Style {
+                foreground: 7,
+                background: 12,
+                decoration: vec![Decoration::Reverse],
+            },
+            &match note.is_empty() {
+                true => format!("{}{} ", label, self.graphemes[start..end].concat()),
+                false => {
+                    let text = format!("{}{}", label, self.graphemes[start..end].concat());
+                    fit(&text, width.saturating_sub(note.width())) + &note
+                }
+            },
+            width,
+        );
+
+        let x = label.width() + widths[start..self.cursor].iter().sum::<usize>();
+
+        (footer, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal::Event;
+    use crate::ui::prompt::Prompt;
+
+    fn typed(text: &str) -> Prompt {
+        let mut prompt = Prompt::new();
+        prompt.open("FIND");
+        prompt.update(&Event::Input(text.to_string()));
+        prompt
+    }
+
+    #[test]
+    fn edits_at_cursor() {
+        let mut prompt = typed("helo");
+
+        prompt.update(&Event::Left);
+        prompt.update(&Event::Input("l".to_string()));
+        assert_eq!(prompt.text(), "hello");
+
+        prompt.update(&Event::Home);
+        prompt.update(&Event::Delete);
+        prompt.update(&Event::End);
+        prompt.update(&Event::Backspace);
+        assert_eq!(prompt.text(), "ell");
+    }
+
+    #[test]
+    fn deletes_words() {
+        let mut prompt = typed("foo bar.baz");
+
+        prompt.update(&Event::DeleteWordLeft);
+        assert_eq!(prompt.text(), "foo bar.");
+
+        prompt.update(&Event::Home);
+        prompt.update(&Event::DeleteWordRight);
+        assert_eq!(prompt.text(), " bar.");
+    }
+
+    #[test]
+    fn recalls_history() {
+        let mut prompt = typed("first");
+        prompt.remember();
+        prompt.open("FIND");
+        prompt.update(&Event::Input("second".to_string()));
+        prompt.remember();
+
+        prompt.open("FIND");
+        prompt.update(&Event::Input("draft".to_string()));
+        prompt.update(&Event::Up);
+        assert_eq!(prompt.text(), "second");
+        prompt.update(&Event::Up);
+        prompt.update(&Event::Up);
+        assert_eq!(prompt.text(), "first");
+        prompt.update(&Event::Down);
+        prompt.update(&Event::Down);
+        assert_eq!(prompt.text(), "draft");
+    }
+}
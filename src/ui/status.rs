@@ -1,8 +1,12 @@
+use unicode_width::UnicodeWidthStr;
+
 use std::io;
 
 use crate::document::Cursor;
 use crate::document::Document;
-use crate::style::styled;
+use crate::style::fit;
+use crate::style::line;
+use crate::style::Decoration;
 use crate::style::Style;
 use crate::terminal::Event;
 use crate::ui::Component;
@@ -61,25 +65,29 @@ impl Component for Status {
             self.child.document().cursor.y + 1,
             self.child.document().cursor.x + 1
         );
-        let padding =
-            String::from_utf8(vec![b' '; width - status.len() - position.len() - 2]).unwrap();
+
+        // the message is cut off to leave room for the position after it
+        let room = width.saturating_sub(position.len() + 3);
+        let status = fit(&status, status.width().min(room));
+        let padding = " ".repeat(width.saturating_sub(status.width() + position.len() + 2));
 
         let footer_style = match self.error {
             Some(_) => Style {
                 foreground: 7,
                 background: 9,
-                decoration: vec![],
+                decoration: vec![Decoration::Reverse],
             },
             None => Style {
                 foreground: 0,
                 background: 7,
-                decoration: vec![],
+                decoration: vec![Decoration::Reverse],
             },
         };
 
-        let footer = styled(
+        let footer = line(
             &footer_style,
             &format!(" {}{}{} ", status, padding, position),
+            width,
         );
 
         child_window.lines.push(footer);
@@ -96,4 +104,41 @@ impl Component for Status {
     fn document(&mut self) -> &mut Document {
         self.child.document()
     }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.child.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::Document;
+    use crate::terminal::Event;
+    use crate::ui::status::Status;
+    use crate::ui::Component;
+    use crate::ui::TextArea;
+
+    fn footer(status: &mut Status, width: usize) -> String {
+        status
+            .render(width, 3)
+            .lines
+            .last()
+            .unwrap()
+            .iter()
+            .map(|cell| cell.grapheme.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn fits_errors_to_the_screen() {
+        let mut status = Status::new(TextArea::new(Document::blank()));
+        let error = "ファイル not found".to_string();
+
+        status.update(&Event::Error(error), 80).unwrap();
+
+        assert_eq!(footer(&mut status, 32), " ERROR: ファイル not found  1:1 ");
+        assert_eq!(footer(&mut status, 16), " ERROR: フ  1:1 ");
+        assert_eq!(footer(&mut status, 4), " 1:1");
+        assert_eq!(footer(&mut status, 0), "");
+    }
 }
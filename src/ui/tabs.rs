@@ -1,11 +1,16 @@
+use unicode_width::UnicodeWidthStr;
+
 use std::io;
 
 use crate::document::Cursor;
 use crate::document::Document;
-use crate::style::styled;
+use crate::path;
+use crate::style::fit;
+use crate::style::line;
 use crate::style::Decoration;
 use crate::style::Style;
 use crate::terminal::Event;
+use crate::terminal::Mouse;
 use crate::ui::Component;
 use crate::ui::TextArea;
 use crate::ui::Window;
@@ -28,6 +33,24 @@ impl Tabs {
     fn current_child(&mut self) -> &mut Box<dyn Component> {
         self.children.get_mut(self.selected).unwrap()
     }
+
+    fn title(&mut self) -> String {
+        let modified = if self.document().is_dirty() { "*" } else { "" };
+        let readonly = if self.document().readonly {
+            " [read-only]"
+        } else {
+            ""
+        };
+
+        format!(
+            "{}{}{} ({}/{})",
+            self.document().name(),
+            modified,
+            readonly,
+            self.selected + 1,
+            self.children.len()
+        )
+    }
 }
 
 impl Component for Tabs {
@@ -47,11 +70,59 @@ impl Component for Tabs {
                 self.selected += 1;
                 Ok(true)
             }
+            // switches to the tab the file is already open in, if any
+            Event::OpenFile(filename) => {
+                let canonical = path::canonical(filename);
+                let open = self
+                    .children
+                    .iter_mut()
+                    .position(|child| child.document().path() == Some(canonical.as_path()));
+
+                if let Some(index) = open {
+                    self.selected = index;
+                    return Ok(true);
+                }
+
+                let mut document = Document::blank();
+                document.open(filename.clone())?;
+
+                self.children
+                    .insert(self.selected + 1, TextArea::new(document));
+                self.selected += 1;
+                Ok(true)
+            }
             Event::Close => {
                 self.children.remove(self.selected);
+
+                // there is always a tab, so closing the last one leaves a
+                // blank document
+                if self.children.is_empty() {
+                    self.children.push(TextArea::new(Document::blank()));
+                }
+
                 self.selected = (self.selected + self.children.len() - 1) % self.children.len();
-                Ok(false)
+                Ok(true)
+            }
+            // clicking the header left or right of the title switches to the
+            // previous or next tab
+            Event::Mouse(Mouse::Press, x, 0) => {
+                let title = self.title().width().min(width);
+                let start = width.saturating_sub(title) / 2;
+
+                if *x < start {
+                    self.update(&Event::Prev, width)
+                } else if *x >= start + title {
+                    self.update(&Event::Next, width)
+                } else {
+                    Ok(false)
+                }
             }
+            Event::Mouse(mouse, x, y) => match mouse {
+                Mouse::Press | Mouse::Release if *y == 0 => Ok(false),
+                _ => self
+                    .current_child()
+                    .update(&Event::Mouse(mouse.clone(), *x, y.saturating_sub(1)), width),
+            },
             _ => self.current_child().update(e, width),
         }
     }
@@ -59,27 +130,20 @@ impl Component for Tabs {
     fn render(&mut self, width: usize, height: usize) -> Window {
         let mut child_window = self.current_child().render(width, height - 1);
 
-        let text = format!(
-            "{} ({}/{})",
-            self.document().name(),
-            self.selected + 1,
-            self.children.len()
-        );
-        let left = String::from_utf8(vec![b' '; (width - text.len()) / 2]).unwrap();
-        let right = String::from_utf8(vec![b' '; (width - text.len()) / 2]).unwrap();
-        let mut pad = String::new();
-
-        if text.len() + left.len() + right.len() < width {
-            pad = " ".to_string();
-        }
+        // the title is centered, and cut off when it is wider than the
+        // screen
+        let title = self.title();
+        let text = fit(&title, title.width().min(width));
+        let left = " ".repeat(width.saturating_sub(text.width()) / 2);
 
-        let header = styled(
+        let header = line(
             &Style {
                 foreground: 7,
                 background: 0,
                 decoration: vec![Decoration::Italic, Decoration::Underline],
             },
-            &format!("{}{}{}{}", left, text, right, pad),
+            &format!("{}{}", left, text),
+            width,
         );
 
         child_window.lines.insert(0, header);
@@ -96,4 +160,47 @@ impl Component for Tabs {
     fn document(&mut self) -> &mut Document {
         self.current_child().document()
     }
+
+    fn child(&mut self) -> Option<&mut dyn Component> {
+        Some(self.current_child().as_mut())
+    }
+
+    fn select_modified(&mut self) -> bool {
+        for i in 0..self.children.len() {
+            let index = (self.selected + i) % self.children.len();
+
+            if self.children[index].select_modified() {
+                self.selected = index;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::Document;
+    use crate::ui::tabs::Tabs;
+    use crate::ui::Component;
+    use crate::ui::TextArea;
+
+    fn header(tabs: &mut Box<dyn Component>, width: usize) -> String {
+        tabs.render(width, 3).lines[0]
+            .iter()
+            .map(|cell| cell.grapheme.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn fits_the_title_to_the_screen() {
+        let mut document = Document::blank();
+        document.filename = Some("ファイル.txt".to_string());
+        let mut tabs = Tabs::new(TextArea::new(document));
+
+        assert_eq!(header(&mut tabs, 24), "   ファイル.txt (1/1)   ");
+        assert_eq!(header(&mut tabs, 7), "ファイ ");
+        assert_eq!(header(&mut tabs, 0), "");
+    }
 }
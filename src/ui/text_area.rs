@@ -1,82 +1,313 @@
 use std::io;
 
+use crate::config;
 use crate::document::Cursor;
 use crate::document::Document;
-use crate::style::styled;
+use crate::document::Highlight;
+use crate::document::Row;
+use crate::style::line;
+use crate::style::Decoration;
+use crate::style::Line;
 use crate::style::Style;
 use crate::terminal::Event;
-use crate::terminal::CLEAR_LINE;
+use crate::terminal::Mouse;
+use crate::ui::line_index::LineIndex;
 use crate::ui::Component;
 use crate::ui::Window;
 
+fn match_style() -> Style {
+    Style {
+        foreground: 0,
+        background: 3,
+        decoration: vec![Decoration::Underline],
+    }
+}
+
+fn current_match_style() -> Style {
+    Style {
+        foreground: 0,
+        background: 11,
+        decoration: vec![Decoration::Underline, Decoration::Reverse],
+    }
+}
+
 pub struct TextArea {
     document: Document,
+    // the display lines of each row, caught up with the document before
+    // handling each event and drawing
+    index: LineIndex,
     window_offset: usize,
+    height: usize,
+    scroll_off: usize,
 }
 
 impl TextArea {
     pub fn new(document: Document) -> Box<TextArea> {
         Box::new(TextArea {
             document: document,
+            index: LineIndex::new(),
             window_offset: 0,
+            height: 0,
+            scroll_off: config::get().scroll_off,
         })
     }
 
+    // Returns the display line and column the cursor is drawn at.
+    fn cursor_position(&self, width: usize) -> Cursor {
+        let cursor = self.document.cursor;
+
+        if self.document.is_empty() {
+            return Cursor { x: 0, y: 0 };
+        }
+
+        let (row_line, col) = self.document.row(cursor.y).layout(width)[cursor.x];
+
+        Cursor {
+            x: col,
+            y: self.index.start_of(cursor.y) + row_line,
+        }
+    }
+
+    // Returns the document position drawn closest to column `x` of display
+    // line `y`, or the end of the document if `y` is past the last line.
+    fn position_at(&self, x: usize, y: usize, width: usize) -> Cursor {
+        let (row, first_line) = self.index.row_at(y);
+
+        if row >= self.document.line_count() {
+            return match self.document.line_count() {
+                0 => Cursor { x: 0, y: 0 },
+                rows => Cursor {
+                    x: self.document.line_len(rows - 1),
+                    y: rows - 1,
+                },
+            };
+        }
+
+        let target = y - first_line;
+        let mut position = Cursor { x: 0, y: row };
+
+        for (j, &(line, col)) in self.document.row(row).layout(width).iter().enumerate() {
+            if line > target {
+                break;
+            }
+
+            if line == target && col <= x {
+                position.x = j;
+            }
+        }
+
+        position
+    }
+
+    // Returns the scroll-off margin, shrunk so it fits on screen.
+    fn margin(&self) -> usize {
+        std::cmp::min(self.scroll_off, self.height.saturating_sub(1) / 2)
+    }
+
+    fn max_offset(&self) -> usize {
+        self.index.total().saturating_sub(self.height)
+    }
+
+    // Moves the view so the cursor is at least the scroll-off margin away
+    // from the top and bottom of the screen, except at the ends of the
+    // document.
+    fn follow_cursor(&mut self, width: usize) {
+        let cursor = self.cursor_position(width);
+        let margin = self.margin();
+
+        if cursor.y < self.window_offset + margin {
+            self.window_offset = cursor.y.saturating_sub(margin);
+        }
+
+        if cursor.y + margin + 1 > self.window_offset + self.height {
+            self.window_offset = std::cmp::min(
+                cursor.y + margin + 1 - self.height,
+                std::cmp::max(self.max_offset(), self.window_offset),
+            );
+        }
+    }
+
+    // Scrolls the view by `lines` display lines, moving the cursor along only
+    // if it would otherwise end up inside the scroll-off margin.
+    fn scroll(&mut self, lines: isize, width: usize) {
+        self.window_offset = self
+            .window_offset
+            .saturating_add_signed(lines)
+            .min(self.max_offset());
+
+        let cursor = self.cursor_position(width);
+        let margin = self.margin();
+
+        let top = if self.window_offset == 0 {
+            0
+        } else {
+            self.window_offset + margin
+        };
+        let bottom = if self.window_offset >= self.max_offset() {
+            self.index.total()
+        } else {
+            self.window_offset + self.height - margin
+        };
+
+        if cursor.y < top {
+            self.document.cursor = self.position_at(cursor.x, top, width);
+        } else if cursor.y >= bottom {
+            self.document.cursor = self.position_at(cursor.x, bottom.saturating_sub(1), width);
+        }
+    }
+
+    // Moves the cursor and the view by a screen, less one line for context.
+    fn page(&mut self, direction: isize, width: usize) {
+        let lines = std::cmp::max(self.height.saturating_sub(1), 1) as isize * direction;
+        let cursor = self.cursor_position(width);
+        let last = self.index.total().saturating_sub(1);
+
+        self.window_offset = self
+            .window_offset
+            .saturating_add_signed(lines)
+            .min(self.max_offset());
+        self.document.cursor = self.position_at(
+            cursor.x,
+            cursor.y.saturating_add_signed(lines).min(last),
+            width,
+        );
+    }
+
+    // Scrolls so the cursor is in the middle of the screen.
+    fn center(&mut self, width: usize) {
+        let cursor = self.cursor_position(width);
+
+        self.window_offset = cursor
+            .y
+            .saturating_sub(self.height / 2)
+            .min(self.max_offset());
+    }
+
     pub fn up(&mut self, width: usize) {
-        if self.document.cursor.x >= width {
-            self.document.cursor.x -= width;
+        let cursor = self.cursor_position(width);
+
+        if cursor.y == 0 {
+            self.document.start_of_line();
         } else {
-            self.document.up();
-            self.document.cursor.x += (self.document.current_line_len() / width) * width;
+            self.document.cursor = self.position_at(cursor.x, cursor.y - 1, width);
         }
     }
 
     pub fn down(&mut self, width: usize) {
-        if self.document.rows.len() == 0 {
+        if self.document.is_empty() {
             return;
         }
 
-        if self.document.cursor.x + width < self.document.current_line_len() {
-            self.document.cursor.x += width;
+        let cursor = self.cursor_position(width);
+
+        if cursor.y + 1 >= self.index.total() {
+            self.document.end_of_line();
         } else {
-            self.document.cursor.x = self.document.cursor.x % width;
-            self.document.down();
+            self.document.cursor = self.position_at(cursor.x, cursor.y + 1, width);
         }
     }
 }
 
 impl Component for TextArea {
     fn update(&mut self, event: &Event, width: usize) -> io::Result<bool> {
+        self.index.update(&mut self.document, width);
+
+        match event {
+            Event::Input(_) | Event::Tab | Event::Delete | Event::Backspace => {}
+            _ => self.document.seal_history(),
+        }
+
         match event {
             Event::Input(c) => {
                 self.document.insert(c);
             }
 
             Event::Up => {
+                self.document.deselect();
                 self.up(width);
             }
             Event::Down => {
+                self.document.deselect();
                 self.down(width);
             }
             Event::Left => {
+                self.document.deselect();
                 self.document.left();
             }
             Event::Right => {
+                self.document.deselect();
                 self.document.right();
             }
 
             Event::PageUp => {
-                // TODO: reimplement
+                self.document.deselect();
+                self.page(-1, width);
             }
             Event::PageDown => {
-                // TODO: reimplement
+                self.document.deselect();
+                self.page(1, width);
+            }
+            Event::ScrollUp => {
+                self.scroll(-1, width);
+            }
+            Event::ScrollDown => {
+                self.scroll(1, width);
+            }
+            Event::Center => {
+                self.center(width);
             }
             Event::Home => {
+                self.document.deselect();
                 self.document.start_of_line();
             }
             Event::End => {
+                self.document.deselect();
+                self.document.end_of_line();
+            }
+
+            Event::SelectUp => {
+                self.document.select();
+                self.up(width);
+            }
+            Event::SelectDown => {
+                self.document.select();
+                self.down(width);
+            }
+            Event::SelectLeft => {
+                self.document.select();
+                self.document.left();
+            }
+            Event::SelectRight => {
+                self.document.select();
+                self.document.right();
+            }
+            Event::SelectHome => {
+                self.document.select();
+                self.document.start_of_line();
+            }
+            Event::SelectEnd => {
+                self.document.select();
                 self.document.end_of_line();
             }
+            Event::WordLeft => {
+                self.document.deselect();
+                self.document.word_left();
+            }
+            Event::WordRight => {
+                self.document.deselect();
+                self.document.word_right();
+            }
+            Event::SelectWordLeft => {
+                self.document.select();
+                self.document.word_left();
+            }
+            Event::SelectWordRight => {
+                self.document.select();
+                self.document.word_right();
+            }
+            Event::SelectAll => {
+                self.document.select_all();
+            }
 
             Event::Tab => {
                 self.document.tab();
@@ -87,10 +318,48 @@ impl Component for TextArea {
             Event::Backspace => {
                 self.document.delete_prev();
             }
+            Event::DeleteWordLeft => {
+                self.document.delete_word_prev();
+            }
+            Event::DeleteWordRight => {
+                self.document.delete_word_next();
+            }
             Event::Enter => {
                 self.document.insert_line();
             }
 
+            Event::Paste(text) => {
+                self.document.paste(text);
+            }
+
+            Event::Mouse(Mouse::Press, x, y) => {
+                if *y >= self.height {
+                    return Ok(false);
+                }
+
+                self.document.deselect();
+                self.document.cursor = self.position_at(*x, self.window_offset + y, width);
+            }
+            Event::Mouse(Mouse::Drag, x, y) => {
+                let y = std::cmp::min(*y, self.height.saturating_sub(1));
+
+                self.document.select();
+                self.document.cursor = self.position_at(*x, self.window_offset + y, width);
+            }
+            Event::Mouse(Mouse::ScrollUp, _, _) => {
+                self.scroll(-3, width);
+            }
+            Event::Mouse(Mouse::ScrollDown, _, _) => {
+                self.scroll(3, width);
+            }
+
+            Event::Undo => {
+                self.document.undo();
+            }
+            Event::Redo => {
+                self.document.redo();
+            }
+
             _ => {
                 return Ok(false);
             }
@@ -100,55 +369,90 @@ impl Component for TextArea {
     }
 
     fn render(&mut self, width: usize, height: usize) -> Window {
-        let mut lines: Vec<String> = vec![];
-        let mut cursor = Cursor { x: 0, y: 0 };
-
         if width == 0 {
             return Window {
-                lines: lines,
-                cursor: cursor,
+                lines: vec![],
+                cursor: Cursor { x: 0, y: 0 },
             };
         }
 
-        for (i, row) in self.document.rows.iter().enumerate() {
-            let split_lines = row.split(width, std::str::from_utf8(CLEAR_LINE).unwrap());
+        self.height = height;
+        self.index.update(&mut self.document, width);
+        self.follow_cursor(width);
 
-            if i == self.document.cursor.y {
-                cursor.x = self.document.cursor_display_x() % width;
-                cursor.y = lines.len() + self.document.cursor_display_x() / width;
+        let mut cursor = self.cursor_position(width);
+        cursor.y = cursor.y - self.window_offset;
+
+        // only the rows on screen are split into display lines, starting
+        // with the one the top of the screen is part of
+        let (first_row, first_line) = self.index.row_at(self.window_offset);
+        let skip = self.window_offset - first_line;
+        let last_row = (first_row + height).min(self.document.line_count());
+        let text = self.document.rows_text(first_row, last_row);
+        let mut lines: Vec<Line> = vec![];
+
+        let selection = self.document.selection();
+        let matches = &self.document.matches;
+        // the first match that doesn't end before the row being drawn
+        let mut first_match = 0;
+
+        for (i, line) in (first_row..).zip(text.split_terminator('\n')) {
+            let row = Row::new(line.to_string());
+            let mut highlights = vec![];
+
+            while first_match < matches.len() && matches[first_match].1.y < i {
+                first_match += 1;
             }
 
-            lines.extend(split_lines);
-        }
+            for (j, (start, end)) in matches.iter().enumerate().skip(first_match) {
+                if start.y > i {
+                    break;
+                }
 
-        if cursor.y < self.window_offset {
-            self.window_offset = cursor.y;
-        }
+                let style = match self.document.current_match {
+                    Some(current) if current == j => current_match_style(),
+                    _ => match_style(),
+                };
 
-        if cursor.y > self.window_offset + height - 1 {
-            self.window_offset = cursor.y - height + 1;
-        }
+                highlights.push((*start, *end, Highlight::Style(style)));
+            }
 
-        cursor.y = cursor.y - self.window_offset;
+            if let Some((start, end)) = selection {
+                highlights.push((start, end, Highlight::Inverted));
+            }
 
-        let last_line = std::cmp::min(self.window_offset + height, lines.len());
+            let ranges: Vec<(usize, usize, Highlight)> = highlights
+                .into_iter()
+                .filter(|(start, end, _)| i >= start.y && i <= end.y)
+                .map(|(start, end, highlight)| {
+                    (
+                        if i == start.y { start.x } else { 0 },
+                        if i == end.y { end.x } else { row.len() },
+                        highlight,
+                    )
+                })
+                .collect();
+
+            lines.extend(row.split(width, &ranges));
+
+            if lines.len() >= skip + height {
+                break;
+            }
+        }
 
-        let visible_lines = &mut lines[self.window_offset..last_line].to_vec();
+        let mut visible_lines: Vec<Line> = lines.into_iter().skip(skip).take(height).collect();
 
-        for _ in last_line..(self.window_offset + height) {
+        while visible_lines.len() < height {
             let style = &Style {
                 foreground: 7,
                 background: 234,
                 decoration: vec![],
             };
-            visible_lines.push(styled(
-                style,
-                &format!("~{}", std::str::from_utf8(CLEAR_LINE).unwrap()),
-            ));
+            visible_lines.push(line(style, "~", width));
         }
 
         Window {
-            lines: visible_lines.clone(),
+            lines: visible_lines,
             cursor: cursor,
         }
     }
@@ -156,4 +460,16 @@ impl Component for TextArea {
     fn document(&mut self) -> &mut Document {
         &mut self.document
     }
+
+    fn select_modified(&mut self) -> bool {
+        self.document.is_dirty()
+    }
+
+    fn window_offset(&mut self) -> usize {
+        self.window_offset
+    }
+
+    fn set_window_offset(&mut self, offset: usize) {
+        self.window_offset = offset;
+    }
 }
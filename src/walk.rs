@@ -0,0 +1,303 @@
+use std::fs;
+use std::path::Path;
+
+// A pattern from a .gitignore file. Patterns containing a `/` other than a
+// trailing one are matched against the path relative to the directory of
+// the .gitignore, and the rest against the name of each entry.
+struct Rule {
+    base: String,
+    pattern: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(base: &str, line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line);
+
+        if pattern.is_empty() {
+            return None;
+        }
+
+        Some(Rule {
+            base: base.to_string(),
+            pattern: pattern.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let relative = if self.base.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(&self.base)
+                .and_then(|p| p.strip_prefix('/'))
+            {
+                Some(relative) => relative,
+                None => return false,
+            }
+        };
+
+        let text: Vec<char> = if self.anchored {
+            relative.chars().collect()
+        } else {
+            relative
+                .rsplit('/')
+                .next()
+                .unwrap_or(relative)
+                .chars()
+                .collect()
+        };
+
+        glob(&self.pattern, &text)
+    }
+}
+
+// Matches the `[...]` class at the start of `pattern` against `c`, returning
+// whether it matched and the length of the class, or None if the class
+// isn't closed.
+fn class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+
+    if negated {
+        i += 1;
+    }
+
+    let start = i;
+    let mut matched = false;
+
+    while i < pattern.len() {
+        if pattern[i] == ']' && i > start {
+            return Some((matched != negated, i + 1));
+        }
+
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&end| end != ']') {
+            matched |= pattern[i] <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+
+    None
+}
+
+// Matches gitignore style globs, where `*` and `?` don't match `/` but `**`
+// matches any number of directories.
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+
+            // `a/**/b` also matches `a/b`
+            if rest.first() == Some(&'/') && glob(&rest[1..], text) {
+                return true;
+            }
+
+            (0..=text.len()).any(|i| glob(rest, &text[i..]))
+        }
+        Some('*') => {
+            let end = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=end).any(|i| glob(&pattern[1..], &text[i..]))
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && glob(&pattern[1..], &text[1..]),
+        Some('[') if !text.is_empty() => match class(pattern, text[0]) {
+            Some((matched, len)) => matched && glob(&pattern[len..], &text[1..]),
+            None => text[0] == '[' && glob(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && glob(&pattern[2..], &text[1..])
+        }
+        Some(&c) => text.first() == Some(&c) && glob(&pattern[1..], &text[1..]),
+    }
+}
+
+// The rules from the .gitignore files of the directories being walked.
+struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    fn add(&mut self, base: &str, text: &str) {
+        self.rules
+            .extend(text.lines().filter_map(|line| Rule::parse(base, line)));
+    }
+
+    // Later rules override earlier ones, and rules from deeper directories
+    // come after those of their parents.
+    fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+
+        for rule in &self.rules {
+            if rule.matches(path, is_dir) {
+                ignored = !rule.negated;
+            }
+        }
+
+        ignored
+    }
+}
+
+fn visit(root: &Path, dir: &str, ignore: &mut Ignore, files: &mut Vec<String>, limit: usize) {
+    let path = root.join(dir);
+    let rules = ignore.rules.len();
+
+    if let Ok(text) = fs::read_to_string(path.join(".gitignore")) {
+        ignore.add(dir, &text);
+    }
+
+    let mut entries: Vec<(String, bool)> = match fs::read_dir(&path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                // symlinks aren't followed so links can't form loops
+                let is_dir = entry.file_type().ok()?.is_dir();
+                Some((name, is_dir))
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    entries.sort();
+
+    for (name, is_dir) in entries {
+        if files.len() >= limit {
+            break;
+        }
+
+        if name == ".git" {
+            continue;
+        }
+
+        let relative = if dir.is_empty() {
+            name
+        } else {
+            format!("{}/{}", dir, name)
+        };
+
+        if ignore.is_ignored(&relative, is_dir) {
+            continue;
+        }
+
+        if is_dir {
+            visit(root, &relative, ignore, files, limit);
+        } else {
+            files.push(relative);
+        }
+    }
+
+    ignore.rules.truncate(rules);
+}
+
+// Returns the paths, relative to `root`, of up to `limit` files under it
+// that aren't excluded by a .gitignore.
+pub fn walk(root: &Path, limit: usize) -> Vec<String> {
+    let mut files = vec![];
+    let mut ignore = Ignore { rules: vec![] };
+
+    visit(root, "", &mut ignore, &mut files, limit);
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixture::Fixture;
+    use crate::walk::glob;
+    use crate::walk::walk;
+    use crate::walk::Ignore;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob(&pattern, &text)
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "ui/main.rs"));
+        assert!(matches("src/**/*.rs", "src/ui/tabs.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("**/foo", "a/b/foo"));
+        assert!(matches("a?c", "abc"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[!a-c]x", "bx"));
+        assert!(matches("\\*", "*"));
+    }
+
+    #[test]
+    fn applies_rules_in_order() {
+        let mut ignore = Ignore { rules: vec![] };
+        ignore.add("", "# comment\n*.log\n!keep.log\n/build/\ntarget\n");
+        ignore.add("docs", "/drafts\n");
+
+        assert!(ignore.is_ignored("a/debug.log", false));
+        assert!(!ignore.is_ignored("a/keep.log", false));
+        assert!(ignore.is_ignored("build", true));
+        assert!(!ignore.is_ignored("build", false));
+        assert!(!ignore.is_ignored("src/build", true));
+        assert!(ignore.is_ignored("src/target", true));
+        assert!(ignore.is_ignored("docs/drafts", true));
+        assert!(!ignore.is_ignored("drafts", true));
+        assert!(!ignore.is_ignored("docs/a/drafts", true));
+    }
+
+    #[test]
+    fn walks_without_ignored_files() {
+        let fixture = Fixture::new(
+            "walk",
+            &[
+                (".git/HEAD", ""),
+                (".gitignore", "/target\n*.log\n"),
+                ("debug.log", ""),
+                ("src/main.rs", ""),
+                ("src/.gitignore", "!keep.log\n"),
+                ("src/keep.log", ""),
+                ("src/ui/tabs.rs", ""),
+                ("target/debug/takkun", ""),
+            ],
+        );
+
+        assert_eq!(
+            walk(&fixture.root, 10000),
+            vec![
+                ".gitignore",
+                "src/.gitignore",
+                "src/keep.log",
+                "src/main.rs",
+                "src/ui/tabs.rs",
+            ]
+        );
+        assert_eq!(walk(&fixture.root, 2).len(), 2);
+    }
+}